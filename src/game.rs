@@ -3,10 +3,17 @@ use crate::*;
 mod animation;
 mod ball;
 mod court;
+mod cpu;
 mod player;
 mod level;
+mod serve;
+mod trajectory;
 mod world;
 
+pub(crate) use court::CourtSurface;
+pub(crate) use cpu::{CpuDifficulty, CpuStyle};
+pub(crate) use trajectory::PathPoint;
+
 pub(crate) struct GamePlugin;
 
 impl Plugin for GamePlugin {
@@ -16,6 +23,8 @@ impl Plugin for GamePlugin {
             .add_plugin(ball::BallPlugin)
             .add_plugin(court::CourtPlugin)
             .add_plugin(player::PlayerPlugin)
+            .add_plugin(cpu::CpuPlugin)
+            .add_plugin(serve::ServePlugin)
             .add_plugin(animation::AnimationPlugin);
     }
 }
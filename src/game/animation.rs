@@ -4,7 +4,12 @@ pub(crate) struct AnimationPlugin;
 
 impl Plugin for AnimationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(SystemSet::on_update(AppState::InGame).with_system(advance_animations));
+        app.add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .after(HitSystems)
+                .with_system(advance_animations),
+        );
     }
 }
 
@@ -49,6 +54,10 @@ impl SpriteAnimation {
         Self::new([13, 14], [0.05, 0.2], false)
     }
 
+    pub(crate) fn opponent_serve() -> Self {
+        Self::new([16, 17, 18, 19], [1.0, 0.3, 0.2, 0.2], false)
+    }
+
     pub(crate) fn opponent_idle() -> Self {
         Self::new([20, 21, 22, 23], [0.3, 0.1, 0.2, 0.1], true)
     }
@@ -68,7 +77,7 @@ impl SpriteAnimation {
 }
 
 fn advance_animations(
-    time: Res<Time>,
+    time: Res<GameTime>,
     mut query: Query<(&mut SpriteAnimation, &mut TextureAtlasSprite)>,
 ) {
     for (mut animation, mut sprite) in query.iter_mut() {
@@ -4,23 +4,52 @@ pub(crate) struct BallPlugin;
 
 impl Plugin for BallPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_update(AppState::InGame)
+        app.add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .label(HitSystems)
+                .after(ApplyIntentSystems)
                 .with_system(ball_spawner)
                 .with_system(hit_ball_system),
         );
     }
 }
 
+/// The velocity that lands the ball on `target`, moving across the court at `speed` unless it
+/// has to be slower to clear the net.
+pub(crate) fn shot_velocity(from: Vec3, target: Vec2, speed: f32) -> Vec3 {
+    const NET_CLEARANCE: f32 = 0.3;
+    let delta = target - from.truncate();
+    let mut flight_time = (delta.length() / speed).max(0.1);
+    let net_fraction = (Y_NETLINE - from.y) / delta.y;
+    if (0.0..1.0).contains(&net_fraction) {
+        // Height at the net grows with the flight time, so solve for the shortest flight
+        // that still passes over it.
+        let clear_z = NET_HEIGHT + BALL_RADIUS + NET_CLEARANCE;
+        let min_time_squared = 2.0 * (clear_z - from.z - (BALL_RADIUS - from.z) * net_fraction)
+            / (GRAVITY * net_fraction * (1.0 - net_fraction));
+        flight_time = flight_time.max(min_time_squared.max(0.0).sqrt());
+    }
+    let vz = (BALL_RADIUS - from.z + 0.5 * GRAVITY * flight_time.powi(2)) / flight_time;
+    (delta / flight_time).extend(vz)
+}
+
 fn hit_ball_system(
     mut bounces: ResMut<BallBouncesSinceHit>,
+    mut touched_net: ResMut<BallTouchedNet>,
+    mut rally: ResMut<RallyState>,
     mut events: EventReader<HitEvent>,
-    mut ball_query: Query<&mut RigidBodyVelocityComponent, With<GameBall>>,
+    mut ball_query: Query<(&mut RigidBodyVelocityComponent, &mut LastHitBy), With<GameBall>>,
 ) {
     for ev in events.iter() {
         bounces.0 = 0;
+        touched_net.0 = false;
+        rally.shots += 1;
+        rally.landing = None;
         info!("bounces reset");
-        let mut ball_velocity = ball_query.get_mut(ev.ball_id).expect("ball not found");
+        let (mut ball_velocity, mut last_hit) =
+            ball_query.get_mut(ev.ball_id).expect("ball not found");
+        *last_hit = LastHitBy(ev.player);
         *ball_velocity = RigidBodyVelocity {
             linvel: ev.new_velocity.into(),
             ..default()
@@ -31,39 +60,38 @@ fn hit_ball_system(
 
 fn ball_spawner(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    asset_server: Option<Res<AssetServer>>,
+    texture_atlases: Option<ResMut<Assets<TextureAtlas>>>,
     mut events: EventReader<SpawnBallEvent>,
 ) {
-    let texture_handle = asset_server.get_handle("textures/ball.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(8.0, 8.0), 1, 6);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    // there's nothing to draw with in headless mode
+    let texture_atlas_handle =
+        asset_server
+            .zip(texture_atlases)
+            .map(|(asset_server, mut texture_atlases)| {
+                let texture_handle = asset_server.get_handle("textures/ball.png");
+                let texture_atlas =
+                    TextureAtlas::from_grid(texture_handle, Vec2::new(8.0, 8.0), 1, 6);
+                texture_atlases.add(texture_atlas)
+            });
 
     for ev in events.iter() {
         let ball_id = commands
             .spawn()
             .insert(GameBall)
+            .insert(SceneEntity)
             .insert(LastHitBy(Player::User))
-            .insert_bundle(SpriteSheetBundle {
-                sprite: TextureAtlasSprite {
-                    index: 0,
-                    ..Default::default()
-                },
-                texture_atlas: texture_atlas_handle.clone(),
-                transform: Transform::from_scale(Vec3::splat(PX_SCALE))
-                    .with_rotation(Quat::from_axis_angle(Vec3::Z, -0.7)),
-                ..Default::default()
-            })
             .insert_bundle(RigidBodyBundle {
                 position: ev.position.0.into(),
                 velocity: ev.velocity.into(),
                 ..Default::default()
             })
             .insert_bundle(ColliderBundle {
-                shape: ColliderShape::ball(1.0).into(),
+                shape: ColliderShape::ball(BALL_RADIUS).into(),
+                flags: ActiveEvents::CONTACT_EVENTS.into(),
                 material: ColliderMaterial {
                     friction: 0.8,
-                    restitution: 0.6,
+                    restitution: BALL_RESTITUTION,
                     ..Default::default()
                 }
                 .into(),
@@ -75,6 +103,20 @@ fn ball_spawner(
                 SyncWorldPosition,
             ))
             .id();
+        let texture_atlas_handle = match &texture_atlas_handle {
+            Some(handle) => handle,
+            None => continue,
+        };
+        commands.entity(ball_id).insert_bundle(SpriteSheetBundle {
+            sprite: TextureAtlasSprite {
+                index: 0,
+                ..Default::default()
+            },
+            texture_atlas: texture_atlas_handle.clone(),
+            transform: Transform::from_scale(Vec3::splat(PX_SCALE))
+                .with_rotation(Quat::from_axis_angle(Vec3::Z, -0.7)),
+            ..Default::default()
+        });
         commands
             .spawn_bundle((
                 Shadow {
@@ -84,10 +126,10 @@ fn ball_spawner(
                 WorldPosition(Vec3::new(ev.position.0.x, ev.position.0.y, 0.)),
                 SyncWorldPosition,
                 GameBallShadow,
+                SceneEntity,
             ))
             .insert(WorldSprite {
                 base: Vec2::new(-0., -8.),
-                ..default()
             })
             .insert_bundle(SpriteSheetBundle {
                 sprite: TextureAtlasSprite {
@@ -1,105 +1,478 @@
+use super::serve::in_service_box;
 use crate::*;
 
 pub(crate) struct CourtPlugin;
 
 impl Plugin for CourtPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_update(AppState::InGame)
+        app.add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .label(CallSystems)
+                .label(PointSystems)
                 .with_system(court_spawner_system)
-                .with_system(handle_bounces_system),
+                .with_system(classify_contacts_system.label(ContactSystems))
+                .with_system(
+                    surface_bounce_system
+                        .label(CallStep::Bounce)
+                        .after(ContactSystems),
+                )
+                .with_system(
+                    handle_bounces_system
+                        .label(CallStep::Bounce)
+                        .after(ContactSystems),
+                )
+                .with_system(
+                    handle_wall_hits_system
+                        .label(CallStep::Wall)
+                        .after(CallStep::Bounce),
+                )
+                .with_system(
+                    handle_player_touches_system
+                        .label(CallStep::Touch)
+                        .after(CallStep::Wall),
+                )
+                .with_system(dead_ball_system.after(CallStep::Touch)),
         );
     }
 }
 
+/// The order calls are made in, for contacts in the same tick that would each end the point.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+enum CallStep {
+    Bounce,
+    Wall,
+    Touch,
+}
+
+/// How the court looks and plays.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CourtSurface {
+    pub(crate) name: &'static str,
+    pub(crate) texture: &'static str,
+    pub(crate) friction: f32,
+    pub(crate) restitution: f32,
+    /// Multiplier on player speed.
+    pub(crate) traction: f32,
+    /// Fraction of the ball's horizontal speed kept through a bounce.
+    pub(crate) speed_retention: f32,
+}
+
+impl Default for CourtSurface {
+    fn default() -> Self {
+        Self::grass()
+    }
+}
+
+impl CourtSurface {
+    /// Fast and low.
+    pub(crate) fn grass() -> Self {
+        Self {
+            name: "grass",
+            texture: "textures/court_grass.png",
+            friction: 0.9,
+            restitution: 0.45,
+            traction: 0.95,
+            speed_retention: 0.95,
+        }
+    }
+
+    /// Slow and high.
+    pub(crate) fn clay() -> Self {
+        Self {
+            name: "clay",
+            texture: "textures/court_clay.png",
+            friction: 0.9,
+            restitution: 0.65,
+            traction: 0.85,
+            speed_retention: 0.75,
+        }
+    }
+
+    pub(crate) fn hard_blue() -> Self {
+        Self {
+            name: "hard-blue",
+            texture: "textures/court_hard_blue.png",
+            friction: 0.9,
+            restitution: 0.55,
+            traction: 1.0,
+            speed_retention: 0.85,
+        }
+    }
+
+    pub(crate) fn hard_green() -> Self {
+        Self {
+            name: "hard-green",
+            texture: "textures/court_hard_green.png",
+            ..Self::hard_blue()
+        }
+    }
+
+    /// A hard court that plays a little quicker and bouncier.
+    pub(crate) fn concrete() -> Self {
+        Self {
+            name: "concrete",
+            texture: "textures/court_concrete.png",
+            friction: 0.9,
+            restitution: 0.6,
+            traction: 1.0,
+            speed_retention: 0.9,
+        }
+    }
+}
+
+impl Preset for CourtSurface {
+    fn presets() -> Vec<Self> {
+        vec![
+            Self::grass(),
+            Self::clay(),
+            Self::hard_blue(),
+            Self::hard_green(),
+            Self::concrete(),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Sorts the ball's contacts with court colliders, and its overlaps with players' bodies, into
+/// game events.
+fn classify_contacts_system(
+    (mut contact_events, mut intersection_events): (
+        EventReader<ContactEvent>,
+        EventReader<IntersectionEvent>,
+    ),
+    ball_query: Query<&WorldPosition, With<GameBall>>,
+    other_query: Query<(Option<&Surface>, Option<&Player>)>,
+    mut bounce_events: EventWriter<BounceEvent>,
+    mut net_events: EventWriter<NetTouchEvent>,
+    mut wall_events: EventWriter<WallHitEvent>,
+    mut player_events: EventWriter<PlayerTouchEvent>,
+) {
+    let contacts = contact_events.iter().filter_map(|ev| match ev {
+        ContactEvent::Started(handle_a, handle_b) => Some((*handle_a, *handle_b)),
+        ContactEvent::Stopped(..) => None,
+    });
+    let overlaps = intersection_events
+        .iter()
+        .filter(|ev| ev.intersecting)
+        .map(|ev| (ev.collider1, ev.collider2));
+    for (handle_a, handle_b) in contacts.chain(overlaps) {
+        let (entity_a, entity_b) = (handle_a.entity(), handle_b.entity());
+        let (ball_pos, other) = match (ball_query.get(entity_a), ball_query.get(entity_b)) {
+            (Ok(ball_pos), _) => (ball_pos, entity_b),
+            (_, Ok(ball_pos)) => (ball_pos, entity_a),
+            _ => continue,
+        };
+        let position = ball_pos.0;
+        match other_query.get(other) {
+            Ok((Some(Surface::Floor), _)) => bounce_events.send(BounceEvent { position }),
+            Ok((Some(Surface::Net), _)) => net_events.send(NetTouchEvent),
+            Ok((Some(Surface::Wall), _)) => wall_events.send(WallHitEvent),
+            Ok((None, Some(player))) => player_events.send(PlayerTouchEvent { player: *player }),
+            _ => warn!("ball touched an untagged collider"),
+        }
+    }
+}
+
+/// Whether a ball that landed out at `position` went long or wide.
+fn out_reason(hitter: Player, position: Vec3) -> PointReason {
+    let long = match hitter {
+        Player::User => position.y > Y_FAR_BASELINE,
+        Player::Opponent => position.y < Y_NEAR_BASELINE,
+    };
+    if long {
+        PointReason::OutLong
+    } else {
+        PointReason::OutWide
+    }
+}
+
 fn handle_bounces_system(
-    mut contact_events: EventReader<ContactEvent>,
+    (mut bounce_events, mut net_events): (EventReader<BounceEvent>, EventReader<NetTouchEvent>),
     mut bounces_counter: ResMut<BallBouncesSinceHit>,
-    ball_query: Query<(&WorldPosition, &LastHitBy), With<GameBall>>,
+    mut touched_net: ResMut<BallTouchedNet>,
+    mut rally: ResMut<RallyState>,
+    (score, setup): (Res<MatchScore>, Res<MatchSetup>),
+    ball_query: Query<&LastHitBy, With<GameBall>>,
+    (mut point_over_events, mut fault_events, mut let_events): (
+        EventWriter<PointOverEvent>,
+        EventWriter<FaultEvent>,
+        EventWriter<LetEvent>,
+    ),
+) {
+    // net touches are handled first so a net cord and the bounce after it can share a frame
+    for _ in net_events.iter() {
+        touched_net.0 = true;
+    }
+    for ev in bounce_events.iter() {
+        let last_hit = match ball_query.get_single() {
+            Ok(last_hit) => last_hit,
+            Err(_) => continue,
+        };
+        match rally.phase {
+            RallyPhase::Serve if last_hit.0 == score.server() => {
+                if !in_service_box(&score, ev.position) {
+                    rally.phase = RallyPhase::Dead;
+                    fault_events.send(FaultEvent {
+                        landing: Some(ev.position),
+                    });
+                    continue;
+                }
+                if touched_net.0 {
+                    rally.phase = RallyPhase::Dead;
+                    let_events.send(LetEvent);
+                    continue;
+                }
+                rally.phase = RallyPhase::Rally;
+            }
+            RallyPhase::Serve | RallyPhase::Rally => {
+                rally.phase = RallyPhase::Rally;
+            }
+            RallyPhase::AwaitingServe | RallyPhase::Toss | RallyPhase::Dead => continue,
+        }
+        bounces_counter.0 += 1;
+        let double_bounce = bounces_counter.0 == 2;
+        let landing = *rally.landing.get_or_insert(ev.position);
+        let (x_min, x_max) = if setup.doubles {
+            (X_DOUBLES_LINE_LEFT, X_DOUBLES_LINE_RIGHT)
+        } else {
+            (X_SINGLES_LINE_LEFT, X_SINGLES_LINE_RIGHT)
+        };
+        let y_min = match last_hit.0 {
+            Player::User => Y_NETLINE,
+            Player::Opponent => Y_NEAR_BASELINE,
+        };
+        let y_max = match last_hit.0 {
+            Player::User => Y_FAR_BASELINE,
+            Player::Opponent => Y_NETLINE,
+        };
+        let inbounds = ev.position.x >= x_min
+            && ev.position.x <= x_max
+            && ev.position.y >= y_min
+            && ev.position.y <= y_max;
+        let on_hitters_side = match last_hit.0 {
+            Player::User => ev.position.y < Y_NETLINE,
+            Player::Opponent => ev.position.y > Y_NETLINE,
+        };
+        let (winner, reason) = match (inbounds, double_bounce) {
+            (true, false) => continue,
+            (_, true) if rally.shots == 1 => (last_hit.0, PointReason::Ace),
+            (_, true) => (last_hit.0, PointReason::Winner),
+            (false, false) if on_hitters_side && touched_net.0 => {
+                (last_hit.0.other(), PointReason::Net)
+            }
+            (false, false) if on_hitters_side => (last_hit.0.other(), PointReason::UnforcedError),
+            (false, false) => (last_hit.0.other(), out_reason(last_hit.0, ev.position)),
+        };
+        info!("the winner is: {winner:?} ({reason:?})");
+        rally.phase = RallyPhase::Dead;
+        point_over_events.send(PointOverEvent {
+            winner,
+            reason,
+            landing: Some(landing),
+            last_hitter: last_hit.0,
+        });
+    }
+}
+
+fn handle_wall_hits_system(
+    mut wall_events: EventReader<WallHitEvent>,
+    bounces: Res<BallBouncesSinceHit>,
+    mut rally: ResMut<RallyState>,
+    ball_query: Query<&LastHitBy, With<GameBall>>,
     mut point_over_events: EventWriter<PointOverEvent>,
+    mut fault_events: EventWriter<FaultEvent>,
 ) {
-    for ev in contact_events.iter() {
-        match ev {
-            ContactEvent::Started(_, _) => {
-                bounces_counter.0 += 1;
-                let double_bounce = bounces_counter.0 == 2;
-                let (ball_pos, last_hit) = ball_query.single();
-                let x_min = X_SINGLES_LINE_LEFT;
-                let x_max = X_SINGLES_LINE_RIGHT;
-                let y_min = match last_hit.0 {
-                    Player::User => Y_NETLINE,
-                    Player::Opponent => Y_NEAR_BASELINE,
-                };
-                let y_max = match last_hit.0 {
-                    Player::User => Y_FAR_BASELINE,
-                    Player::Opponent => Y_NETLINE,
-                };
-                dbg!(ball_pos);
-                dbg!((x_min, x_max));
-                dbg!((y_min, y_max));
-                let inbounds = ball_pos.0.x >= x_min
-                    && ball_pos.0.x <= x_max
-                    && ball_pos.0.y >= y_min
-                    && ball_pos.0.y <= y_max;
-                dbg!(inbounds);
-                let winner = match (&last_hit.0, inbounds, double_bounce) {
-                    (Player::User, true, false) | (Player::Opponent, true, false) => continue,
-                    (Player::Opponent, false, false) => Player::User,
-                    (Player::User, false, false) => Player::Opponent,
-                    (Player::Opponent, _, true) => Player::Opponent,
-                    (Player::User, _, true) => Player::User,
-                };
-                info!("the winner is: {winner:?}");
-                point_over_events.send(PointOverEvent { winner });
+    for _ in wall_events.iter() {
+        let last_hit = match ball_query.get_single() {
+            Ok(last_hit) => last_hit,
+            Err(_) => continue,
+        };
+        // a ball that bounced in before reaching the wall couldn't be returned
+        let (winner, reason) = match bounces.0 {
+            0 => (last_hit.0.other(), PointReason::OutLong),
+            _ if rally.shots == 1 => (last_hit.0, PointReason::Ace),
+            _ => (last_hit.0, PointReason::Winner),
+        };
+        match rally.phase {
+            RallyPhase::Serve if bounces.0 == 0 => {
+                rally.phase = RallyPhase::Dead;
+                fault_events.send(FaultEvent { landing: None });
+            }
+            RallyPhase::Serve | RallyPhase::Rally => {
+                info!("the winner is: {winner:?} ({reason:?})");
+                rally.phase = RallyPhase::Dead;
+                point_over_events.send(PointOverEvent {
+                    winner,
+                    reason,
+                    landing: rally.landing,
+                    last_hitter: last_hit.0,
+                });
             }
-            ContactEvent::Stopped(_, _) => {}
+            RallyPhase::AwaitingServe | RallyPhase::Toss | RallyPhase::Dead => {}
+        }
+    }
+}
+
+/// Ends the point against a player the ball touches. A serve touching the server's own team
+/// is a fault, and one touching the receiver before it bounces wins the point.
+fn handle_player_touches_system(
+    mut touch_events: EventReader<PlayerTouchEvent>,
+    score: Res<MatchScore>,
+    mut rally: ResMut<RallyState>,
+    ball_query: Query<&LastHitBy, With<GameBall>>,
+    mut point_over_events: EventWriter<PointOverEvent>,
+    mut fault_events: EventWriter<FaultEvent>,
+) {
+    for ev in touch_events.iter() {
+        let last_hit = match ball_query.get_single() {
+            Ok(last_hit) => last_hit,
+            Err(_) => continue,
+        };
+        match rally.phase {
+            RallyPhase::Serve if ev.player == score.server() => {
+                rally.phase = RallyPhase::Dead;
+                fault_events.send(FaultEvent { landing: None });
+            }
+            RallyPhase::Serve | RallyPhase::Rally => {
+                let winner = ev.player.other();
+                info!("the winner is: {winner:?} (touched {:?})", ev.player);
+                rally.phase = RallyPhase::Dead;
+                point_over_events.send(PointOverEvent {
+                    winner,
+                    reason: PointReason::Touched,
+                    landing: rally.landing,
+                    last_hitter: last_hit.0,
+                });
+            }
+            // the toss starts out inside the server
+            RallyPhase::AwaitingServe | RallyPhase::Toss | RallyPhase::Dead => {}
+        }
+    }
+}
+
+/// Below this speed, a ball that has touched the net is resting against it.
+const DEAD_BALL_SPEED: f32 = 0.5;
+/// How long a ball rests against the net before it's dead.
+const DEAD_BALL_SECS: f32 = 0.5;
+
+/// Ends the point when the ball comes to rest against the net. Only a ball in play is counted,
+/// so each serve and rally starts resting from nothing.
+fn dead_ball_system(
+    time: Res<GameTime>,
+    touched_net: Res<BallTouchedNet>,
+    mut rally: ResMut<RallyState>,
+    mut resting_secs: Local<f32>,
+    ball_query: Query<(&RigidBodyVelocityComponent, &LastHitBy), With<GameBall>>,
+    mut point_over_events: EventWriter<PointOverEvent>,
+    mut fault_events: EventWriter<FaultEvent>,
+) {
+    let (velocity, last_hit) = match ball_query.get_single() {
+        Ok(ball) if matches!(rally.phase, RallyPhase::Serve | RallyPhase::Rally) => ball,
+        _ => {
+            *resting_secs = 0.;
+            return;
+        }
+    };
+    if !touched_net.0 || velocity.linvel.norm() > DEAD_BALL_SPEED {
+        *resting_secs = 0.;
+        return;
+    }
+    *resting_secs += time.delta_seconds();
+    if *resting_secs < DEAD_BALL_SECS {
+        return;
+    }
+    *resting_secs = 0.;
+    match rally.phase {
+        RallyPhase::Serve => {
+            rally.phase = RallyPhase::Dead;
+            fault_events.send(FaultEvent { landing: None });
+        }
+        RallyPhase::Rally => {
+            info!("dead ball");
+            rally.phase = RallyPhase::Dead;
+            point_over_events.send(PointOverEvent {
+                winner: last_hit.0.other(),
+                reason: PointReason::Net,
+                landing: None,
+                last_hitter: last_hit.0,
+            });
+        }
+        RallyPhase::AwaitingServe | RallyPhase::Toss | RallyPhase::Dead => {}
+    }
+}
+
+/// Takes off the horizontal speed the surface soaks up on each bounce.
+fn surface_bounce_system(
+    surface: Res<CourtSurface>,
+    mut bounce_events: EventReader<BounceEvent>,
+    mut ball_query: Query<&mut RigidBodyVelocityComponent, With<GameBall>>,
+) {
+    for _ in bounce_events.iter() {
+        for mut velocity in ball_query.iter_mut() {
+            velocity.linvel.x *= surface.speed_retention;
+            velocity.linvel.y *= surface.speed_retention;
         }
     }
 }
 
 fn court_spawner_system(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    asset_server: Option<Res<AssetServer>>,
+    surface: Res<CourtSurface>,
     mut events: EventReader<SpawnCourtEvent>,
 ) {
     for _ in events.iter() {
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: asset_server.load("textures/net.png"),
-                transform: Transform::from_scale(Vec3::splat(PX_SCALE)),
-                ..Default::default()
-            })
-            .insert_bundle((
-                WorldPosition(Vec3::new(0.0, Y_NETLINE, 0.0)),
-                SyncWorldPosition,
-                WorldSprite {
-                    base: Vec2::new(0.0, -22.0),
-                },
-            ));
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: asset_server.load("textures/court_grass.png"),
+        // headless mode only needs the colliders
+        if let Some(asset_server) = &asset_server {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    texture: asset_server.load("textures/net.png"),
+                    transform: Transform::from_scale(Vec3::splat(PX_SCALE)),
+                    ..Default::default()
+                })
+                .insert_bundle((
+                    SceneEntity,
+                    WorldPosition(Vec3::new(0.0, Y_NETLINE, 0.0)),
+                    SyncWorldPosition,
+                    WorldSprite {
+                        base: Vec2::new(0.0, -22.0),
+                    },
+                ));
+        }
+        let mut court = commands.spawn();
+        court.insert(SceneEntity);
+        if let Some(asset_server) = &asset_server {
+            court.insert_bundle(SpriteBundle {
+                texture: asset_server.load(surface.texture),
                 transform: Transform::from_scale(Vec3::splat(PX_SCALE)),
                 ..Default::default()
-            })
-            .with_children(|parent| {
-                // floor
-                parent.spawn_bundle(ColliderBundle {
+            });
+        }
+        court.with_children(|parent| {
+            // floor
+            parent
+                .spawn_bundle(ColliderBundle {
                     shape: ColliderShape::cuboid(200.0, 200.0, 10.0).into(),
                     flags: ActiveEvents::CONTACT_EVENTS.into(),
                     position: (Vec3::new(0.0, 0.0, -10.0), Quat::IDENTITY).into(),
                     material: ColliderMaterial {
-                        friction: 0.9,
-                        restitution: 0.5,
+                        friction: surface.friction,
+                        restitution: surface.restitution,
                         ..Default::default()
                     }
                     .into(),
                     ..Default::default()
-                });
-                // wall
-                parent.spawn_bundle(ColliderBundle {
+                })
+                .insert(Surface::Floor);
+            // wall
+            parent
+                .spawn_bundle(ColliderBundle {
                     shape: ColliderShape::cuboid(200.0, 1.0, 200.0).into(),
-                    position: (Vec3::new(0.0, 15.0, 0.0), Quat::IDENTITY).into(),
+                    position: (Vec3::new(0.0, Y_FAR_WALL + 1.0, 0.0), Quat::IDENTITY).into(),
                     material: ColliderMaterial {
                         friction: 0.6,
                         restitution: 0.8,
@@ -107,9 +480,11 @@ fn court_spawner_system(
                     }
                     .into(),
                     ..Default::default()
-                });
-                // net
-                parent.spawn_bundle(ColliderBundle {
+                })
+                .insert(Surface::Wall);
+            // net
+            parent
+                .spawn_bundle(ColliderBundle {
                     position: (
                         Vec3::new(X_CENTER_LINE, Y_NETLINE, NET_HEIGHT / 2.0),
                         Quat::IDENTITY,
@@ -128,7 +503,179 @@ fn court_spawner_system(
                     }
                     .into(),
                     ..Default::default()
-                });
-            });
+                })
+                .insert(Surface::Net);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestGame;
+
+    const DROP_HEIGHT: f32 = 3.;
+    /// Ticks allowed past when the ball should land, for the bounce to be seen to.
+    const SPARE_TICKS: u32 = 5;
+
+    /// Drops the ball straight down onto `spot`, as last hit by the near player.
+    fn drop_ball(game: &mut TestGame, spot: Vec2) {
+        game.spawn_ball(spot.extend(DROP_HEIGHT), Vec3::ZERO, Player::User);
+    }
+
+    /// Ticks for a dropped ball to fall until it touches the court.
+    fn fall_ticks() -> u32 {
+        let secs = (2. * (DROP_HEIGHT - BALL_RADIUS) / GRAVITY).sqrt();
+        (secs / PHYSICS_STEP_SECS).ceil() as u32
+    }
+
+    /// Ticks for a dropped ball to bounce a second time, at most. It never bounces back higher
+    /// than it fell from, so each bounce comes within twice the fall of the one before.
+    fn second_bounce_ticks() -> u32 {
+        3 * fall_ticks() + SPARE_TICKS
+    }
+
+    fn first_point(game: &TestGame) -> Option<(Player, PointReason)> {
+        game.points.first().map(|ev| (ev.winner, ev.reason))
+    }
+
+    #[test]
+    fn ball_in_is_played_on_until_it_bounces_twice() {
+        let mut game = TestGame::new();
+        drop_ball(&mut game, Vec2::new(5., 0.));
+        // past the first bounce
+        game.advance(fall_ticks() + SPARE_TICKS);
+        assert_eq!(first_point(&game), None);
+        game.advance(second_bounce_ticks());
+        assert_eq!(
+            first_point(&game),
+            Some((Player::User, PointReason::Winner))
+        );
+    }
+
+    #[test]
+    fn serve_bouncing_twice_is_an_ace() {
+        let mut game = TestGame::new();
+        game.resource_mut::<RallyState>().shots = 1;
+        drop_ball(&mut game, Vec2::new(5., 0.));
+        game.advance(second_bounce_ticks());
+        assert_eq!(first_point(&game), Some((Player::User, PointReason::Ace)));
+    }
+
+    #[test]
+    fn calls_balls_out() {
+        let cases = [
+            (Vec2::new(0., 12.5), PointReason::OutLong),
+            (Vec2::new(17., 0.), PointReason::OutWide),
+            (Vec2::new(0., -12.), PointReason::UnforcedError),
+        ];
+        for (spot, reason) in cases {
+            let mut game = TestGame::new();
+            drop_ball(&mut game, spot);
+            game.advance(fall_ticks() + SPARE_TICKS);
+            assert_eq!(
+                first_point(&game),
+                Some((Player::Opponent, reason)),
+                "ball dropped at {spot}"
+            );
+        }
+    }
+
+    #[test]
+    fn ball_touching_a_player_loses_their_team_the_point() {
+        let mut game = TestGame::new();
+        let spot = Vec2::new(3., -10.);
+        game.spawn_player(Player::User, spot.extend(0.));
+        game.spawn_ball(spot.extend(DROP_HEIGHT), Vec3::ZERO, Player::Opponent);
+        game.advance(SPARE_TICKS);
+        assert_eq!(
+            first_point(&game),
+            Some((Player::Opponent, PointReason::Touched))
+        );
+    }
+
+    #[test]
+    fn doubles_alleys_are_in() {
+        let mut game = TestGame::new();
+        game.resource_mut::<MatchSetup>().doubles = true;
+        drop_ball(&mut game, Vec2::new(17., 0.));
+        game.advance(fall_ticks() + SPARE_TICKS);
+        assert_eq!(first_point(&game), None);
+    }
+
+    /// Sends the ball from just in front of the net, on the near side, straight into it.
+    fn hit_into_net(game: &mut TestGame, speed: f32) {
+        let from = Vec3::new(0., Y_NETLINE - 1.2, NET_HEIGHT - BALL_RADIUS / 2.);
+        game.spawn_ball(from, Vec3::Y * speed, Player::User);
+    }
+
+    fn dead_ball_ticks() -> u32 {
+        (DEAD_BALL_SECS / PHYSICS_STEP_SECS).ceil() as u32
+    }
+
+    #[test]
+    fn ball_into_the_net_loses_the_hitter_the_point() {
+        let mut game = TestGame::new();
+        hit_into_net(&mut game, 8.);
+        game.advance(60);
+        assert_eq!(
+            first_point(&game),
+            Some((Player::Opponent, PointReason::Net))
+        );
+    }
+
+    #[test]
+    fn serve_into_the_net_is_a_fault() {
+        let mut game = TestGame::new();
+        game.await_serve(Player::User);
+        *game.resource_mut::<RallyState>() = RallyState {
+            phase: RallyPhase::Serve,
+            shots: 1,
+            ..default()
+        };
+        hit_into_net(&mut game, 8.);
+        game.advance(60);
+        assert_eq!(first_point(&game), None);
+        let rally = game.resource_mut::<RallyState>();
+        assert_eq!(rally.phase, RallyPhase::AwaitingServe);
+        assert_eq!(rally.attempt, ServeAttempt::Second);
+    }
+
+    /// Rolls the ball gently into the net, so it comes away too slowly to be played. Returns
+    /// once it has touched the net.
+    fn rest_against_net(game: &mut TestGame) {
+        game.without_gravity();
+        hit_into_net(game, 0.5);
+        game.advance(30);
+        assert!(game.resource_mut::<BallTouchedNet>().0);
+    }
+
+    #[test]
+    fn ball_resting_against_the_net_is_dead() {
+        let mut game = TestGame::new();
+        rest_against_net(&mut game);
+        game.advance(dead_ball_ticks() + SPARE_TICKS);
+        assert_eq!(
+            first_point(&game),
+            Some((Player::Opponent, PointReason::Net))
+        );
+    }
+
+    #[test]
+    fn resting_starts_over_with_each_rally() {
+        let mut game = TestGame::new();
+        rest_against_net(&mut game);
+        game.advance(dead_ball_ticks() / 2);
+        // the point ends some other way, and the next rally starts with the ball at the net
+        game.resource_mut::<RallyState>().phase = RallyPhase::Dead;
+        game.advance(1);
+        game.resource_mut::<RallyState>().phase = RallyPhase::Rally;
+        game.advance(dead_ball_ticks() - SPARE_TICKS);
+        assert_eq!(first_point(&game), None);
+        game.advance(2 * SPARE_TICKS);
+        assert_eq!(
+            first_point(&game),
+            Some((Player::Opponent, PointReason::Net))
+        );
     }
 }
@@ -0,0 +1,552 @@
+use super::player::{sweet_spot, swing_target};
+use super::serve::{active_slot, in_service_box};
+use super::trajectory::{predict, BallPhysics, HittingZone};
+use crate::*;
+use rand::Rng;
+
+/// How far out a predicted landing may be and still get chased, since predictions aren't exact.
+const LINE_CALL_MARGIN: f32 = 0.5;
+/// How far past the line a CPU's deliberate miss lands.
+const MISS_MARGIN: f32 = 1.5;
+
+pub(crate) struct CpuPlugin;
+
+impl Plugin for CpuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .label(IntentSystems)
+                .after(PointSystems)
+                .with_system(cpu_plan_system.label(CpuStep::Plan))
+                .with_system(
+                    cpu_movement_system
+                        .label(CpuStep::Move)
+                        .after(CpuStep::Plan),
+                )
+                .with_system(cpu_swing_system.after(CpuStep::Move)),
+        );
+    }
+}
+
+/// A CPU moves and swings by the plan it makes this tick.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+enum CpuStep {
+    Plan,
+    Move,
+}
+
+/// How well CPU players play.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CpuDifficulty {
+    pub(crate) name: &'static str,
+    /// Multiplier on player speed.
+    pub(crate) speed: f32,
+    /// How long after a shot the CPU starts moving.
+    pub(crate) reaction_secs: f32,
+    /// Multiplier on how far from the sweet spot the CPU can still hit the ball.
+    pub(crate) reach: f32,
+    /// Chance a return is hit out.
+    pub(crate) error_rate: f32,
+    /// Largest miss of the aimed spot on returns that stay in.
+    pub(crate) spread: f32,
+    /// Fraction of a full charge the CPU builds before each shot.
+    pub(crate) power: f32,
+}
+
+impl Default for CpuDifficulty {
+    fn default() -> Self {
+        Self::club()
+    }
+}
+
+impl CpuDifficulty {
+    pub(crate) fn rookie() -> Self {
+        Self {
+            name: "rookie",
+            speed: 0.4,
+            reaction_secs: 0.4,
+            reach: 0.75,
+            error_rate: 0.25,
+            spread: 8.0,
+            power: 0.3,
+        }
+    }
+
+    pub(crate) fn club() -> Self {
+        Self {
+            name: "club",
+            speed: 0.5,
+            reaction_secs: 0.2,
+            reach: 1.0,
+            error_rate: 0.1,
+            spread: 5.0,
+            power: 0.5,
+        }
+    }
+
+    pub(crate) fn pro() -> Self {
+        Self {
+            name: "pro",
+            speed: 0.7,
+            reaction_secs: 0.1,
+            reach: 1.25,
+            error_rate: 0.04,
+            spread: 3.0,
+            power: 0.7,
+        }
+    }
+
+    /// Gets to everything and never misses.
+    pub(crate) fn unfair() -> Self {
+        Self {
+            name: "unfair",
+            speed: 1.0,
+            reaction_secs: 0.0,
+            reach: 1.75,
+            error_rate: 0.0,
+            spread: 1.0,
+            power: 0.85,
+        }
+    }
+}
+
+impl Preset for CpuDifficulty {
+    fn presets() -> Vec<Self> {
+        vec![Self::rookie(), Self::club(), Self::pro(), Self::unfair()]
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Where CPU players like to stand. The selected style applies to every CPU player.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum CpuStyle {
+    /// Stays back and only comes in behind short balls.
+    #[default]
+    Baseliner,
+    /// Follows serves and approach shots to the net to volley.
+    ServeAndVolley,
+    /// Stays deep and plays slow, safe returns.
+    Pusher,
+}
+
+impl Preset for CpuStyle {
+    fn presets() -> Vec<Self> {
+        vec![Self::Baseliner, Self::ServeAndVolley, Self::Pusher]
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Baseliner => "baseliner",
+            Self::ServeAndVolley => "serve-and-volley",
+            Self::Pusher => "pusher",
+        }
+    }
+}
+
+impl CpuStyle {
+    /// How far inside its own baseline a CPU of this style has to be when it hits to follow
+    /// the shot in to the net, if it ever does.
+    fn approach_depth(self) -> Option<f32> {
+        match self {
+            Self::Baseliner => Some(8.),
+            Self::ServeAndVolley => Some(-2.),
+            Self::Pusher => None,
+        }
+    }
+
+    /// How far behind the baseline a CPU of this style waits.
+    fn home_depth(self) -> f32 {
+        match self {
+            Self::Baseliner | Self::ServeAndVolley => 1.,
+            Self::Pusher => 3.,
+        }
+    }
+}
+
+/// Picks the next shot for `player`, a CPU: aimed away from `opponents_x`, the mean x of the
+/// players on the other side, with a charge set by the difficulty. A miss lands either past
+/// the sideline in play or, hit at full charge, past the baseline. Pushers aim closer to the
+/// middle, hit softer and miss half as often.
+fn choose_shot(
+    player: Player,
+    opponents_x: f32,
+    setup: &MatchSetup,
+    difficulty: &CpuDifficulty,
+    style: CpuStyle,
+    rng: &mut impl Rng,
+) -> CpuShot {
+    let (width, power, error_rate) = match style {
+        CpuStyle::Pusher => (0.4, difficulty.power * 0.6, difficulty.error_rate / 2.),
+        CpuStyle::Baseliner | CpuStyle::ServeAndVolley => {
+            (0.8, difficulty.power, difficulty.error_rate)
+        }
+    };
+    let side = if opponents_x > X_CENTER_LINE { -1. } else { 1. };
+    let mut aim = side * width * (0.5 + 0.5 * rng.gen::<f32>());
+    let mut charge = power;
+    let mut target = None;
+    if rng.gen::<f32>() < error_rate {
+        if rng.gen() {
+            aim = side;
+            let y = swing_target(player, aim, charge).y;
+            target = Some(Vec2::new(side * (sideline(setup) + MISS_MARGIN), y));
+        } else {
+            charge = 1.;
+            let baseline = match player.other() {
+                Player::User => Y_NEAR_BASELINE,
+                Player::Opponent => Y_FAR_BASELINE,
+            };
+            let x = swing_target(player, aim, charge).x;
+            target = Some(Vec2::new(x, baseline + forward(player) * MISS_MARGIN));
+        }
+    } else {
+        let noise = rng.gen_range(-1.0..=1.0) * difficulty.spread;
+        aim = (aim + noise / X_SINGLES_LINE_RIGHT).clamp(-1., 1.);
+    }
+    CpuShot {
+        aim,
+        charge_secs: charge * PLAYER_FULL_CHARGE_SECS,
+        target,
+    }
+}
+
+/// How far `position` is inside `player`'s baseline.
+fn depth_inside_baseline(player: Player, position: Vec3) -> f32 {
+    match player {
+        Player::User => position.y - Y_NEAR_BASELINE,
+        Player::Opponent => Y_FAR_BASELINE - position.y,
+    }
+}
+
+/// Where a CPU player waits when it isn't chasing the ball.
+fn home_position(player: Player, plan: &CpuPlan, zone: Option<&CoverZone>) -> Vec2 {
+    let x = zone.map_or(X_CENTER_LINE, |zone| (zone.x_min + zone.x_max) / 2.);
+    let y = if plan.at_net {
+        Y_NETLINE - forward(player) * 5.
+    } else {
+        match player {
+            Player::User => Y_NEAR_BASELINE - plan.style.home_depth(),
+            Player::Opponent => Y_FAR_BASELINE + plan.style.home_depth(),
+        }
+    };
+    Vec2::new(x, y)
+}
+
+/// Direction along y that `player` hits toward.
+fn forward(player: Player) -> f32 {
+    match player {
+        Player::User => 1.,
+        Player::Opponent => -1.,
+    }
+}
+
+/// How far from the center line the sidelines in play are.
+fn sideline(setup: &MatchSetup) -> f32 {
+    if setup.doubles {
+        X_DOUBLES_LINE_RIGHT
+    } else {
+        X_SINGLES_LINE_RIGHT
+    }
+}
+
+/// Whether a ball landing at `position` on `player`'s side would be in play.
+fn worth_returning(
+    position: Vec3,
+    player: Player,
+    rally: &RallyState,
+    score: &MatchScore,
+    setup: &MatchSetup,
+) -> bool {
+    if rally.phase == RallyPhase::Serve {
+        return in_service_box(score, position);
+    }
+    let x_max = sideline(setup);
+    let (y_min, y_max) = match player {
+        Player::User => (Y_NEAR_BASELINE, Y_NETLINE),
+        Player::Opponent => (Y_NETLINE, Y_FAR_BASELINE),
+    };
+    position.x.abs() <= x_max + LINE_CALL_MARGIN
+        && position.y >= y_min - LINE_CALL_MARGIN
+        && position.y <= y_max + LINE_CALL_MARGIN
+}
+
+/// Clears every CPU's net plan before a point and sends a serve-and-volleyer in behind its
+/// serve.
+fn cpu_plan_system(
+    rally: Res<RallyState>,
+    score: Res<MatchScore>,
+    setup: Res<MatchSetup>,
+    mut query: Query<(&Player, &PlayerSlot, &mut CpuPlan)>,
+) {
+    for (player, slot, mut plan) in query.iter_mut() {
+        match rally.phase {
+            RallyPhase::AwaitingServe | RallyPhase::Toss => {
+                plan.at_net = false;
+                plan.intercept = None;
+                plan.facing = None;
+                plan.shot = None;
+            }
+            RallyPhase::Serve
+                if plan.style == CpuStyle::ServeAndVolley
+                    && *player == score.server()
+                    && slot.0 == active_slot(*player, &score, &setup) =>
+            {
+                plan.at_net = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// What `cpu_movement_system` reads and steers of each CPU player.
+type MovingCpu<'a> = (
+    &'a Player,
+    &'a mut CpuPlan,
+    &'a mut PlayerIntent,
+    &'a PlayerState,
+    &'a WorldPosition,
+    &'a PlayerSpeed,
+    Option<&'a CoverZone>,
+);
+
+/// Moves each CPU player toward where it can next hit the ball, or to its home position when
+/// the ball is going away or will land out. A CPU at the net that can't volley the ball, say
+/// because it's been lobbed, gives up the net and backs off to take it after the bounce.
+fn cpu_movement_system(
+    time: Res<GameTime>,
+    (rally, bounces, score, setup): (
+        Res<RallyState>,
+        Res<BallBouncesSinceHit>,
+        Res<MatchScore>,
+        Res<MatchSetup>,
+    ),
+    (difficulty, surface, rapier_config): (
+        Res<CpuDifficulty>,
+        Res<CourtSurface>,
+        Res<RapierConfiguration>,
+    ),
+    mut hits: EventReader<HitEvent>,
+    mut timers: ResMut<CpuTimers>,
+    mut player_query: Query<MovingCpu, (With<CpuControlled>, Without<GameBall>)>,
+    ball_query: Query<(&WorldPosition, &RigidBodyVelocityComponent), With<GameBall>>,
+) {
+    if hits.iter().count() > 0 {
+        timers.secs_since_hit = 0.;
+    } else {
+        timers.secs_since_hit += time.delta_seconds();
+    }
+    let reacting = timers.secs_since_hit < difficulty.reaction_secs;
+    let physics = BallPhysics::new(&rapier_config, &surface);
+    let ball = ball_query.get_single().ok();
+    for (player, mut plan, mut intent, state, position, speed, zone) in player_query.iter_mut() {
+        intent.movement = Vec2::ZERO;
+        if reacting {
+            plan.intercept = None;
+            plan.facing = None;
+        }
+        // a receiver waits where it was put until the serve is struck
+        if reacting
+            || matches!(rally.phase, RallyPhase::AwaitingServe | RallyPhase::Toss)
+            || matches!(*state, PlayerState::Swing | PlayerState::Serve)
+        {
+            continue;
+        }
+        // where the ball can be hit from within `hitting_zone`, if it's coming and will be in
+        let chase = |hitting_zone: HittingZone| -> Option<PathPoint> {
+            let (ball_pos, velocity) = ball?;
+            let velocity: Vec3 = velocity.linvel.into();
+            if velocity.y * forward(*player) >= 0. {
+                return None;
+            }
+            let prediction = predict(ball_pos.0, velocity, bounces.0 > 0, &physics, &hitting_zone);
+            let in_play = prediction.landing.is_none_or(|landing| {
+                worth_returning(landing.position, *player, &rally, &score, &setup)
+            });
+            prediction.intercept.filter(|_| in_play)
+        };
+        let intercept = if plan.at_net {
+            let volley = chase(HittingZone::at_net(*player));
+            let baseline = volley
+                .is_none()
+                .then(|| chase(HittingZone::for_player(*player)));
+            if let Some(Some(_)) = baseline {
+                plan.at_net = false;
+            }
+            volley.or(baseline.flatten())
+        } else {
+            chase(HittingZone::for_player(*player))
+        };
+        plan.intercept = intercept;
+        let mut target = match intercept {
+            Some(intercept) => {
+                // stand beside the ball so it meets the sweet spot, on whichever side the ball
+                // was first seen coming, so the CPU doesn't switch sides as it closes in
+                let ball = intercept.position.truncate();
+                let facing = *plan.facing.get_or_insert(if ball.x >= position.0.x {
+                    PlayerFacing::Right
+                } else {
+                    PlayerFacing::Left
+                });
+                ball - sweet_spot(Vec3::ZERO, &facing).truncate()
+            }
+            None => {
+                plan.facing = None;
+                home_position(*player, &plan, zone)
+            }
+        };
+        // leave balls outside our zone to the partner
+        if let Some(zone) = zone {
+            target.x = target.x.clamp(zone.x_min, zone.x_max);
+        }
+        // full speed until the last step, which is shortened to stop on the spot
+        let delta = target - position.0.truncate();
+        let step = speed.0 * time.delta_seconds();
+        if step > 0. {
+            intent.movement = (delta / step).clamp_length_max(1.);
+        }
+    }
+}
+
+/// What `cpu_swing_system` reads and sets of each CPU player.
+type SwingingCpu<'a> = (
+    &'a Player,
+    &'a PlayerSlot,
+    &'a mut CpuPlan,
+    &'a mut PlayerIntent,
+    &'a PlayerState,
+    &'a PlayerFacing,
+    &'a WorldPosition,
+);
+
+/// Gives the CPU the same charge and release intents as a keyboard player: it picks a shot as
+/// soon as it can reach the ball, starts charging once it's in position and the ball is as far
+/// away in time as the charge it wants, and releases once the ball reaches its sweet spot, or is as
+/// close to it as it will get. If the ball goes away it swings anyway and misses. A serving CPU
+/// tosses after a short wait and aims anywhere in the box.
+fn cpu_swing_system(
+    time: Res<GameTime>,
+    (difficulty, mut rng): (Res<CpuDifficulty>, ResMut<GameRng>),
+    (rally, score, setup): (Res<RallyState>, Res<MatchScore>, Res<MatchSetup>),
+    mut timers: ResMut<CpuTimers>,
+    mut player_query: Query<SwingingCpu, (With<CpuControlled>, Without<GameBall>)>,
+    all_players: Query<(&Player, &WorldPosition), Without<GameBall>>,
+    ball_query: Query<(&WorldPosition, &RigidBodyVelocityComponent), With<GameBall>>,
+) {
+    if rally.phase == RallyPhase::AwaitingServe {
+        timers.serve_wait_secs += time.delta_seconds();
+    } else {
+        timers.serve_wait_secs = 0.;
+    }
+    let ball = ball_query.get_single().ok();
+    for (player, slot, mut plan, mut intent, state, facing, position) in player_query.iter_mut() {
+        intent.start_charge = false;
+        intent.release = false;
+        intent.target = None;
+        if rally.phase == RallyPhase::AwaitingServe {
+            let serving =
+                *player == score.server() && slot.0 == active_slot(*player, &score, &setup);
+            if serving && timers.serve_wait_secs >= CPU_SERVE_DELAY_SECS {
+                intent.start_charge = true;
+                intent.aim = rng.gen_range(-1.0..=1.0);
+            }
+            continue;
+        }
+        match *state {
+            PlayerState::Idle | PlayerState::Run => {
+                let intercept = match plan.intercept {
+                    Some(intercept) => intercept,
+                    None => {
+                        plan.shot = None;
+                        continue;
+                    }
+                };
+                if plan.shot.is_none() {
+                    let opponents: Vec<f32> = all_players
+                        .iter()
+                        .filter(|(other, _)| **other != *player)
+                        .map(|(_, position)| position.0.x)
+                        .collect();
+                    let opponents_x = opponents.iter().sum::<f32>() / opponents.len().max(1) as f32;
+                    plan.shot = Some(choose_shot(
+                        *player,
+                        opponents_x,
+                        &setup,
+                        &difficulty,
+                        plan.style,
+                        &mut *rng,
+                    ));
+                }
+                // charging slows the CPU down, so it only starts once it's where it means to hit
+                let radius = PLAYER_SWEET_SPOT_RADIUS * difficulty.reach;
+                let offset = (sweet_spot(position.0, facing) - intercept.position).truncate();
+                let in_position = offset.length() < radius / 2.;
+                if in_position
+                    && plan
+                        .shot
+                        .is_some_and(|shot| intercept.secs <= shot.charge_secs)
+                {
+                    intent.start_charge = true;
+                }
+            }
+            PlayerState::Charge => {
+                let release = match ball {
+                    Some((ball_pos, velocity)) => {
+                        let sweet_spot = sweet_spot(position.0, facing);
+                        let velocity: Vec3 = velocity.linvel.into();
+                        let distance = (sweet_spot - ball_pos.0).length();
+                        let next = ball_pos.0 + velocity * time.delta_seconds();
+                        let closing = (sweet_spot - next).length() < distance;
+                        let radius = PLAYER_SWEET_SPOT_RADIUS * difficulty.reach;
+                        let incoming = velocity.y * forward(*player) < 0.;
+                        !incoming || (distance < radius && (distance < radius / 2. || !closing))
+                    }
+                    None => true,
+                };
+                if !release {
+                    continue;
+                }
+                intent.release = true;
+                let shot = plan.shot.take();
+                intent.aim = shot.map_or(0., |shot| shot.aim);
+                intent.target = shot.and_then(|shot| shot.target);
+                // come in behind a shot hit from far enough inside the court
+                let depth = depth_inside_baseline(*player, position.0);
+                if plan.style.approach_depth().is_some_and(|min| depth >= min) {
+                    plan.at_net = true;
+                }
+            }
+            PlayerState::Swing | PlayerState::Serve => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestGame;
+
+    #[test]
+    fn receiver_returns_a_serve() {
+        let mut game = TestGame::new();
+        game.await_serve(Player::User);
+        let server = game.spawn_player(Player::User, Vec3::ZERO);
+        game.spawn_cpu(Player::Opponent, Vec3::ZERO);
+        game.set_intent(
+            server,
+            PlayerIntent {
+                start_charge: true,
+                ..default()
+            },
+        );
+        game.advance(5 * 60);
+        let hitters: Vec<_> = game.hits.iter().map(|hit| hit.player).collect();
+        assert!(
+            hitters.starts_with(&[Player::User, Player::Opponent]),
+            "hits by {hitters:?}"
+        );
+    }
+}
@@ -1,4 +1,6 @@
+use super::serve::serve_position;
 use crate::*;
+use rand::Rng;
 
 pub(crate) struct LevelPlugin;
 
@@ -6,90 +8,199 @@ impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup_scene))
             .add_system_set(
-                SystemSet::on_update(AppState::InGame)
-                    .with_system(reset_scene_system)
-                    .with_system(clear_scene_system)
+                SystemSet::on_exit(AppState::InGame)
+                    .with_system(despawn_scene_system)
+                    .with_system(reset_match_system),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::NewMatch).with_system(start_new_match_system),
+            )
+            .add_system_set_to_stage(
+                FixedUpdate,
+                in_state(AppState::InGame)
+                    .after(PointSystems)
+                    .before(IntentSystems)
                     .with_system(update_score_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::PointOver).with_system(start_point_over_system),
+            )
+            .add_system_set_to_stage(
+                FixedUpdate,
+                in_state(AppState::PointOver).with_system(end_point_over_system),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::PointOver).with_system(reset_scene_system))
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(clear_scene_system),
             );
     }
 }
 
+struct PointOverTimer(Timer);
+
+/// Scores the points ended this tick, then holds play to show the call, or ends the match.
 fn update_score_system(
     mut events: EventReader<PointOverEvent>,
     mut game_overs: EventWriter<GameOverEvent>,
-    mut user_score: ResMut<UserScore>,
-    mut opponent_score: ResMut<OpponentScore>,
+    mut state: ResMut<State<AppState>>,
+    mut score: ResMut<MatchScore>,
+    setup: Res<MatchSetup>,
     mut results_text: Query<&mut Text, With<ResultsText>>,
 ) {
+    let mut next = None;
     for ev in events.iter() {
-        match ev.winner {
-            Player::User => user_score.0 += 1,
-            Player::Opponent => opponent_score.0 += 1,
+        info!(
+            "point to {:?} ({:?}), landed at {:?}, last hit by {:?}",
+            ev.winner, ev.reason, ev.landing, ev.last_hitter
+        );
+        let change = score.point_won(ev.winner);
+        info!("{change:?}");
+        next = Some(AppState::PointOver);
+        if let ScoreChange::Match(winner) = change {
+            let text = match (winner, setup.versus(), setup.spectating()) {
+                (Player::User, _, true) => "Near side won!".to_owned(),
+                (Player::Opponent, _, true) => "Far side won!".to_owned(),
+                (Player::User, false, _) => "You won!".to_owned(),
+                (Player::Opponent, false, _) => "You lost!".to_owned(),
+                (Player::User, true, _) => "Player 1 won!".to_owned(),
+                (Player::Opponent, true, _) => "Player 2 won!".to_owned(),
+            };
+            if let Ok(mut results_text) = results_text.get_single_mut() {
+                results_text.sections[0].value = text;
+            }
+            game_overs.send(GameOverEvent);
+            next = Some(AppState::GameOver);
         }
     }
-    let text = &mut results_text.single_mut().sections[0].value;
-    if user_score.0 >= 7 && user_score.0 >= opponent_score.0 + 2 {
-        *text = "You won!".to_owned();
-        game_overs.send(GameOverEvent);
-    } else if opponent_score.0 >= 7 && opponent_score.0 >= user_score.0 + 2 {
-        *text = "You lost!".to_owned();
-        game_overs.send(GameOverEvent);
+    if let Some(next) = next {
+        state.push(next).unwrap();
     }
 }
 
-fn clear_scene_system(
-    mut commands: Commands,
-    mut events: EventReader<GameOverEvent>,
-    query: Query<Entity, Or<(With<Player>, With<Shadow>, With<GameBall>)>>,
+fn start_point_over_system(mut commands: Commands) {
+    commands.insert_resource(PointOverTimer(Timer::from_seconds(POINT_OVER_SECS, false)));
+}
+
+/// Counted in ticks like the rest of the game, so a replay's points last just as long.
+fn end_point_over_system(
+    time: Res<GameTime>,
+    mut timer: ResMut<PointOverTimer>,
+    mut state: ResMut<State<AppState>>,
 ) {
-    for _ in events.iter() {
-        for id in query.iter() {
-            commands.entity(id).despawn();
-        }
+    if timer.0.tick(time.delta()).just_finished() {
+        state.pop().unwrap();
     }
 }
 
+/// Filters for the players, the ball and their shadows.
+type MatchPiece = Or<(With<Player>, With<Shadow>, With<GameBall>)>;
+
+/// Clears the finished match's players and ball off the court.
+fn clear_scene_system(mut commands: Commands, query: Query<Entity, MatchPiece>) {
+    for id in query.iter() {
+        commands.entity(id).despawn();
+    }
+}
+
+/// Takes the ball away and gets ready for the next serve.
 fn reset_scene_system(
     mut commands: Commands,
-    mut events: EventReader<PointOverEvent>,
-    ball_query: Query<Entity, Or<(With<GameBall>, With<GameBallShadow>)>>,
-    mut ball_events: EventWriter<SpawnBallEvent>,
+    ball_query: Query<Entity, BallOrShadow>,
     mut bounces: ResMut<BallBouncesSinceHit>,
+    mut touched_net: ResMut<BallTouchedNet>,
+    mut rally: ResMut<RallyState>,
 ) {
-    for _ in events.iter() {
-        for id in ball_query.iter() {
-            commands.entity(id).despawn();
-        }
-        bounces.0 = 0;
-        ball_events.send(SpawnBallEvent {
-            position: WorldPosition(Vec3::new(X_CENTER_LINE, Y_NEAR_BASELINE, 3.0)),
-            velocity: RigidBodyVelocity {
-                linvel: Vec3::new(10. * rand::random::<f32>() - 5., 15., 10.).into(),
-                ..Default::default()
-            },
-        });
+    for id in ball_query.iter() {
+        commands.entity(id).despawn();
     }
+    bounces.0 = 0;
+    touched_net.0 = false;
+    *rally = RallyState::default();
+}
+
+fn despawn_scene_system(mut commands: Commands, query: Query<Entity, With<SceneEntity>>) {
+    for id in query.iter() {
+        commands.entity(id).despawn_recursive();
+    }
+}
+
+/// Leaves nothing of the match behind for the next one, which starts from `setup_scene`.
+fn reset_match_system(
+    mut score: ResMut<MatchScore>,
+    mut rally: ResMut<RallyState>,
+    mut bounces: ResMut<BallBouncesSinceHit>,
+    mut touched_net: ResMut<BallTouchedNet>,
+    mut cpu_timers: ResMut<CpuTimers>,
+) {
+    *score = default();
+    *rally = default();
+    *bounces = default();
+    *touched_net = default();
+    *cpu_timers = default();
+}
+
+fn start_new_match_system(mut state: ResMut<State<AppState>>) {
+    state.set(AppState::InGame).unwrap();
 }
 
 fn setup_scene(
-    mut ball_events: EventWriter<SpawnBallEvent>,
+    format: Res<MatchFormat>,
+    setup: Res<MatchSetup>,
+    mut rng: ResMut<GameRng>,
+    mut score: ResMut<MatchScore>,
     mut court_events: EventWriter<SpawnCourtEvent>,
     mut player_events: EventWriter<SpawnPlayerEvent>,
 ) {
-    ball_events.send(SpawnBallEvent {
-        position: WorldPosition(Vec3::new(X_CENTER_LINE, Y_NEAR_BASELINE, 3.0)),
-        velocity: RigidBodyVelocity {
-            linvel: Vec3::new(10. * rand::random::<f32>() - 5., 15., 10.).into(),
-            ..Default::default()
-        },
-    });
     court_events.send(SpawnCourtEvent);
-    player_events.send(SpawnPlayerEvent {
-        position: WorldPosition(Vec3::new(0.0, Y_NEAR_BASELINE - 1.0, 0.0)),
-        opponent: false,
-    });
-    player_events.send(SpawnPlayerEvent {
-        position: WorldPosition(Vec3::new(0.0, Y_FAR_BASELINE + 1.0, 0.0)),
-        opponent: true,
-    });
+    // a coin toss decides who serves first
+    let first_server = if rng.gen() {
+        Player::User
+    } else {
+        Player::Opponent
+    };
+    *score = MatchScore::new(format.clone(), first_server);
+    for player in [Player::User, Player::Opponent] {
+        for slot in 0..setup.team_size() {
+            player_events.send(SpawnPlayerEvent {
+                position: WorldPosition(serve_position(player, slot, &score, &setup)),
+                player,
+                slot,
+                controller: setup.controller(player, slot),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::TestGame;
+    use crate::*;
+
+    #[test]
+    fn new_match_starts_from_a_clean_slate() {
+        let mut game = TestGame::new();
+        let scene = game.count::<SceneEntity>();
+        let surfaces = game.count::<Surface>();
+        game.spawn_player(Player::User, Vec3::new(0., -15., 0.));
+        game.spawn_ball(Vec3::new(0., -10., 3.), Vec3::ZERO, Player::User);
+        game.resource_mut::<MatchScore>().point_won(Player::User);
+        game.resource_mut::<BallBouncesSinceHit>().0 = 1;
+        game.resource_mut::<BallTouchedNet>().0 = true;
+
+        game.resource_mut::<State<AppState>>()
+            .replace(AppState::NewMatch)
+            .unwrap();
+        game.advance(2);
+
+        // one court again, and a player a side, without shadows since nothing is drawn headless
+        assert_eq!(game.count::<SceneEntity>(), scene + 2);
+        assert_eq!(game.count::<Surface>(), surfaces);
+        assert_eq!(game.count::<Player>(), 2);
+        assert_eq!(game.count::<GameBall>(), 0);
+        assert_eq!(game.resource_mut::<BallBouncesSinceHit>().0, 0);
+        assert!(!game.resource_mut::<BallTouchedNet>().0);
+        let score = game.resource_mut::<MatchScore>();
+        assert_eq!(score.scoreboard_row(Player::User), "0  0");
+        assert_eq!(score.scoreboard_row(Player::Opponent), "0  0");
+    }
 }
@@ -1,29 +1,71 @@
+use super::ball::shot_velocity;
+use super::serve::side_sign;
 use crate::*;
 
 pub(crate) struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_update(AppState::InGame)
-                .with_system(user_movement_system)
-                .with_system(user_begin_charge_system)
-                .with_system(user_release_charge_system)
-                .with_system(opponent_movement_system)
-                .with_system(opponent_hit_system),
+        app.add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .label(ApplyIntentSystems)
+                .after(ServeSystems)
+                .with_system(player_movement_system.label(PlayerStep::Move))
+                .with_system(
+                    begin_charge_system
+                        .label(PlayerStep::BeginCharge)
+                        .after(PlayerStep::Move),
+                )
+                .with_system(
+                    charge_time_system
+                        .label(PlayerStep::Charge)
+                        .after(PlayerStep::BeginCharge),
+                )
+                .with_system(
+                    release_charge_system
+                        .label(PlayerStep::Release)
+                        .after(PlayerStep::Charge),
+                )
+                .with_system(swing_system.after(PlayerStep::Release)),
         )
-        .add_system_set(
-            SystemSet::on_update(AppState::InGame)
+        .add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .after(ApplyIntentSystems)
+                .with_system(clear_intent_requests_system)
+                .with_system(sync_body_collider_system),
+        )
+        .add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .after(HitSystems)
                 .with_system(player_spawn_system)
-                .with_system(tick_swing_cooldown_system)
-                .with_system(flip_sprite_facing_system)
-                .with_system(turn_player_toward_ball)
-                .with_system(set_player_speed_system)
-                .with_system(update_animation_system),
+                .with_system(tick_swing_cooldown_system.label(PlayerStep::Cooldown))
+                .with_system(
+                    turn_player_toward_ball
+                        .label(PlayerStep::Turn)
+                        .after(PlayerStep::Cooldown),
+                )
+                .with_system(flip_sprite_facing_system.after(PlayerStep::Turn))
+                .with_system(set_player_speed_system.after(PlayerStep::Turn))
+                .with_system(update_animation_system.after(PlayerStep::Turn)),
         );
     }
 }
 
+/// A charge started and released in the same tick still swings, and a player turns to the ball
+/// in the tick its swing cooldown ends.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+enum PlayerStep {
+    Move,
+    BeginCharge,
+    Charge,
+    Release,
+    Cooldown,
+    Turn,
+}
+
 fn update_animation_system(
     mut query: Query<(&Player, &PlayerState, &mut SpriteAnimation), Changed<PlayerState>>,
 ) {
@@ -33,35 +75,34 @@ fn update_animation_system(
             (Player::User, PlayerState::Run) => SpriteAnimation::player_run(),
             (Player::User, PlayerState::Charge) => SpriteAnimation::player_charge(),
             (Player::User, PlayerState::Swing) => SpriteAnimation::player_swing(),
+            (Player::User, PlayerState::Serve) => SpriteAnimation::player_serve(),
             (Player::Opponent, PlayerState::Idle) => SpriteAnimation::opponent_idle(),
             (Player::Opponent, PlayerState::Run) => SpriteAnimation::opponent_run(),
             (Player::Opponent, PlayerState::Charge) => SpriteAnimation::opponent_charge(),
             (Player::Opponent, PlayerState::Swing) => SpriteAnimation::opponent_swing(),
+            (Player::Opponent, PlayerState::Serve) => SpriteAnimation::opponent_serve(),
         };
     }
 }
 
-fn user_movement_system(
-    time: Res<Time>,
-    keyboard: Res<Input<KeyCode>>,
-    mut query: Query<(&mut PlayerState, &PlayerSpeed, &mut WorldPosition), With<UserControlled>>,
+fn player_movement_system(
+    time: Res<GameTime>,
+    rally: Res<RallyState>,
+    mut query: Query<(
+        &PlayerIntent,
+        &mut PlayerState,
+        &PlayerSpeed,
+        &mut WorldPosition,
+    )>,
 ) {
-    for (mut state, speed, mut position) in query.iter_mut() {
-        let mut direction = Vec3::ZERO;
-        if keyboard.pressed(KEY_CODE_RIGHT) {
-            direction += Vec3::X;
-        }
-        if keyboard.pressed(KEY_CODE_LEFT) {
-            direction -= Vec3::X;
-        }
-        if keyboard.pressed(KEY_CODE_UP) {
-            direction += Vec3::Y;
-        }
-        if keyboard.pressed(KEY_CODE_DOWN) {
-            direction -= Vec3::Y;
-        }
+    if rally.phase == RallyPhase::AwaitingServe {
+        return;
+    }
+    for (intent, mut state, speed, mut position) in query.iter_mut() {
+        // already scaled down for a partly tilted stick or a CPU arriving at its spot
+        let direction = intent.movement.clamp_length_max(1.).extend(0.);
         if direction.length() > 0. {
-            position.0 += direction.normalize() * speed.0 * time.delta().as_secs_f32();
+            position.0 += direction * speed.0 * time.delta().as_secs_f32();
             // don't change state while charging or if it's already set
             if matches!(*state, PlayerState::Idle) {
                 *state = PlayerState::Run;
@@ -75,151 +116,148 @@ fn user_movement_system(
     }
 }
 
-fn opponent_movement_system(
-    time: Res<Time>,
-    mut player_query: Query<
-        (&mut PlayerState, &mut WorldPosition, &PlayerSpeed),
-        (With<CpuControlled>, Without<GameBall>),
-    >,
-    ball_query: Query<&WorldPosition, With<GameBall>>,
-) {
-    for (mut opponent_state, mut opponent_pos, speed) in player_query.iter_mut() {
-        if let Ok(ball_pos) = ball_query.get_single() {
-            let delta_x = ball_pos.0.x - opponent_pos.0.x;
-            let direction = if delta_x > 0. { Vec3::X } else { -Vec3::X };
-            let max_distance = speed.0 * time.delta().as_secs_f32();
-            if delta_x.abs() <= max_distance {
-                opponent_pos.0.x = ball_pos.0.x;
-                if matches!(*opponent_state, PlayerState::Run) {
-                    *opponent_state = PlayerState::Idle;
-                }
-            } else {
-                opponent_pos.0 += max_distance * direction;
-                if matches!(*opponent_state, PlayerState::Idle) {
-                    *opponent_state = PlayerState::Run;
-                }
-            }
-        }
+fn set_player_speed_system(mut query: Query<(&mut PlayerSpeed, &TopSpeed, &PlayerState)>) {
+    for (mut player_speed, top_speed, player_state) in query.iter_mut() {
+        player_speed.0 = match player_state {
+            PlayerState::Idle | PlayerState::Run => top_speed.0,
+            PlayerState::Charge => top_speed.0 * PLAYER_CHARGING_SPEED_FACTOR,
+            PlayerState::Swing | PlayerState::Serve => 0.,
+        };
     }
 }
 
-fn opponent_hit_system(
-    mut commands: Commands,
-    mut player_query: Query<
-        (Entity, &mut PlayerState, &WorldPosition),
-        (With<CpuControlled>, Without<GameBall>),
-    >,
-    mut hit_events: EventWriter<HitEvent>,
-    mut ball_query: Query<(Entity, &WorldPosition, &mut LastHitBy), With<GameBall>>,
-) {
-    for (opponent_id, mut opponent_state, opponent_pos) in player_query.iter_mut() {
-        if matches!(*opponent_state, PlayerState::Charge | PlayerState::Swing) {
-            continue;
-        }
-        if let Ok((ball_id, ball_pos, mut last_hit)) = ball_query.get_single_mut() {
-            if (ball_pos.0.y - opponent_pos.0.y).abs() < 0.25 {
-                let delta_x = ball_pos.0.x - opponent_pos.0.x;
-                if delta_x.abs() < 2.0 {
-                    *last_hit = LastHitBy(Player::Opponent);
-                    let return_velocity = Vec3::new(
-                        rand::random::<f32>() * 6.,
-                        rand::random::<f32>() * 2.0 - 17.,
-                        10.,
-                    );
-                    hit_events.send(HitEvent {
-                        new_velocity: return_velocity,
-                        ball_id,
-                    });
-                    *opponent_state = PlayerState::Swing;
-                    commands
-                        .entity(opponent_id)
-                        .insert(SwingCooldown(Timer::from_seconds(1.0, false)));
-                }
-            }
-        }
+/// Clears one-off requests once a tick has acted on them, so a key press that arrives in a
+/// frame without a tick is still acted on, and only once.
+fn clear_intent_requests_system(mut query: Query<&mut PlayerIntent>) {
+    for mut intent in query.iter_mut() {
+        intent.start_charge = false;
+        intent.release = false;
+        intent.target = None;
     }
 }
 
-fn set_player_speed_system(
-    mut query: Query<(&mut PlayerSpeed, &PlayerState), With<UserControlled>>,
+fn begin_charge_system(
+    rally: Res<RallyState>,
+    mut query: Query<(&PlayerIntent, &mut PlayerState)>,
 ) {
-    for (mut player_speed, player_state) in query.iter_mut() {
-        player_speed.0 = match player_state {
-            PlayerState::Idle | PlayerState::Run => PLAYER_SPEED,
-            PlayerState::Charge => PLAYER_SPEED * PLAYER_CHARGING_SPEED_FACTOR,
-            PlayerState::Swing => 0.,
-        };
+    // starting a charge tosses the ball instead while waiting to serve
+    if rally.phase == RallyPhase::AwaitingServe {
+        return;
+    }
+    for (intent, mut state) in query.iter_mut() {
+        if intent.start_charge && matches!(*state, PlayerState::Idle | PlayerState::Run) {
+            *state = PlayerState::Charge;
+        }
     }
 }
 
-fn user_begin_charge_system(
-    keyboard: Res<Input<KeyCode>>,
-    mut query: Query<&mut PlayerState, With<UserControlled>>,
+fn release_charge_system(
+    query: Query<(Entity, &PlayerIntent, &PlayerState)>,
+    mut swing_events: EventWriter<SwingEvent>,
 ) {
-    if keyboard.just_pressed(KEY_CODE_ACTION) {
-        for mut state in query.iter_mut() {
-            if matches!(*state, PlayerState::Idle | PlayerState::Run) {
-                *state = PlayerState::Charge;
-            }
+    for (entity, intent, state) in query.iter() {
+        if intent.release && matches!(*state, PlayerState::Charge) {
+            swing_events.send(SwingEvent {
+                player_id: entity,
+                aim: intent.aim,
+                target: intent.target,
+            });
         }
     }
 }
 
-fn user_release_charge_system(
+/// Fraction of a full charge built up in `secs`.
+pub(crate) fn charge_fraction(secs: f32) -> f32 {
+    (secs / PLAYER_FULL_CHARGE_SECS).min(1.)
+}
+
+/// Where the racket meets the ball for a player standing at `position`.
+pub(crate) fn sweet_spot(position: Vec3, facing: &PlayerFacing) -> Vec3 {
+    let flip = if matches!(facing, PlayerFacing::Left) {
+        -1.0
+    } else {
+        1.0
+    };
+    position + Vec3::new(9.0 * flip, 0.0, 11.0) * PX_SCALE / WORLD_SCALE
+}
+
+/// Where a swing by `player` lands: `aim` picks the spot across the court, just inside the
+/// singles sidelines at full aim, and a longer `charge` sends it deeper. A full charge lands
+/// just inside the baseline.
+pub(crate) fn swing_target(player: Player, aim: f32, charge: f32) -> Vec2 {
+    let (midline, baseline) = match player.other() {
+        Player::User => (Y_NEAR_MIDLINE, Y_NEAR_BASELINE),
+        Player::Opponent => (Y_FAR_MIDLINE, Y_FAR_BASELINE),
+    };
+    Vec2::new(
+        X_CENTER_LINE + aim * (X_SINGLES_LINE_RIGHT - 1.),
+        midline + (0.45 + 0.5 * charge) * (baseline - midline),
+    )
+}
+
+fn charge_time_system(time: Res<GameTime>, mut query: Query<(&PlayerState, &mut ChargeTime)>) {
+    for (state, mut charge) in query.iter_mut() {
+        charge.0 = match state {
+            PlayerState::Charge => charge.0 + time.delta_seconds(),
+            _ => 0.,
+        };
+    }
+}
+
+/// What `swing_system` reads and sets of each swinging player.
+type SwingingPlayer<'a> = (
+    &'a Player,
+    &'a mut PlayerState,
+    &'a PlayerFacing,
+    &'a WorldPosition,
+    &'a ChargeTime,
+    Option<&'a CpuControlled>,
+);
+
+/// Turns a released charge into a swing, for keyboard and CPU players alike. The ball is hit
+/// if it's within reach of the sweet spot, harder and deeper the longer the charge was held.
+/// CPU players reach as far as their difficulty allows.
+fn swing_system(
     mut commands: Commands,
-    keyboard: Res<Input<KeyCode>>,
-    mut player_query: Query<
-        (Entity, &mut PlayerState, &PlayerFacing, &WorldPosition),
-        With<UserControlled>,
-    >,
-    mut ball_query: Query<(Entity, &WorldPosition, &mut LastHitBy), With<GameBall>>,
+    difficulty: Res<CpuDifficulty>,
+    mut swing_events: EventReader<SwingEvent>,
+    mut player_query: Query<SwingingPlayer>,
+    ball_query: Query<(Entity, &WorldPosition), With<GameBall>>,
     mut hit_events: EventWriter<HitEvent>,
 ) {
-    if keyboard.just_released(KEY_CODE_ACTION) {
-        for (entity, mut player_state, player_facing, player_position) in player_query.iter_mut() {
-            if matches!(*player_state, PlayerState::Charge) {
-                *player_state = PlayerState::Swing;
-                commands
-                    .entity(entity)
-                    .insert(SwingCooldown(Timer::from_seconds(
-                        PLAYER_SWING_COOLDOWN_SECS,
-                        false,
-                    )));
-                let flip = if matches!(player_facing, PlayerFacing::Left) {
-                    -1.0
-                } else {
-                    1.0
-                };
-                let sweet_spot =
-                    player_position.0 + Vec3::new(9.0 * flip, 0.0, 11.0) * PX_SCALE / WORLD_SCALE;
-                for (ball_id, ball_pos, mut last_hit) in ball_query.iter_mut() {
-                    let dist_to_ball = (sweet_spot - ball_pos.0).length();
-                    let direction_left = (Vec3::new(X_SINGLES_LINE_LEFT, Y_FAR_BASELINE, 3.)
-                        - player_position.0)
-                        .normalize();
-                    let direction_center = (Vec3::new(X_CENTER_LINE, Y_FAR_BASELINE, 0.)
-                        - player_position.0)
-                        .normalize();
-                    let direction_right = (Vec3::new(X_SINGLES_LINE_RIGHT, Y_FAR_BASELINE, 3.)
-                        - player_position.0)
-                        .normalize();
-                    let hit_direction_xy = if keyboard.pressed(KEY_CODE_LEFT) {
-                        direction_left
-                    } else if keyboard.pressed(KEY_CODE_RIGHT) {
-                        direction_right
-                    } else {
-                        direction_center
-                    };
-                    let direction = 20.0 * hit_direction_xy + Vec3::Z * 7.;
-                    info!("{dist_to_ball:?}");
-                    if dist_to_ball < 2.0 {
-                        *last_hit = LastHitBy(Player::User);
-                        hit_events.send(HitEvent {
-                            ball_id,
-                            new_velocity: direction,
-                        });
-                    }
-                }
+    for ev in swing_events.iter() {
+        let (player, mut state, facing, position, charge_time, cpu) =
+            match player_query.get_mut(ev.player_id) {
+                Ok(player) => player,
+                Err(_) => continue,
+            };
+        if !matches!(*state, PlayerState::Charge) {
+            continue;
+        }
+        *state = PlayerState::Swing;
+        commands
+            .entity(ev.player_id)
+            .insert(SwingCooldown(Timer::from_seconds(
+                PLAYER_SWING_COOLDOWN_SECS,
+                false,
+            )));
+        let charge = charge_fraction(charge_time.0);
+        let sweet_spot = sweet_spot(position.0, facing);
+        let reach = match cpu {
+            Some(_) => PLAYER_SWEET_SPOT_RADIUS * difficulty.reach,
+            None => PLAYER_SWEET_SPOT_RADIUS,
+        };
+        for (ball_id, ball_pos) in ball_query.iter() {
+            if (sweet_spot - ball_pos.0).length() < reach {
+                let target = ev
+                    .target
+                    .unwrap_or_else(|| swing_target(*player, ev.aim, charge));
+                let speed = SHOT_SPEED_MIN + charge * (SHOT_SPEED_MAX - SHOT_SPEED_MIN);
+                hit_events.send(HitEvent {
+                    ball_id,
+                    player: *player,
+                    new_velocity: shot_velocity(ball_pos.0, target, speed),
+                });
             }
         }
     }
@@ -227,7 +265,7 @@ fn user_release_charge_system(
 
 fn tick_swing_cooldown_system(
     mut commands: Commands,
-    time: Res<Time>,
+    time: Res<GameTime>,
     mut query: Query<(Entity, &mut PlayerState, &mut SwingCooldown)>,
 ) {
     for (player_id, mut player_state, mut timer) in query.iter_mut() {
@@ -247,11 +285,23 @@ fn flip_sprite_facing_system(mut query: Query<(&PlayerFacing, &mut TextureAtlasS
     }
 }
 
+/// Turns players to face the ball, except while charging or swinging. A CPU that has picked
+/// which way to face for the coming ball faces that way instead, charging included, so that the
+/// ball meets the sweet spot it lined up.
 fn turn_player_toward_ball(
-    mut player_query: Query<(&mut PlayerFacing, &PlayerState, &WorldPosition)>,
+    mut player_query: Query<(
+        &mut PlayerFacing,
+        &PlayerState,
+        &WorldPosition,
+        Option<&CpuPlan>,
+    )>,
     ball_query: Query<&WorldPosition, With<GameBall>>,
 ) {
-    for (mut player_facing, player_state, player_pos) in player_query.iter_mut() {
+    for (mut player_facing, player_state, player_pos, plan) in player_query.iter_mut() {
+        if let Some(facing) = plan.and_then(|plan| plan.facing) {
+            *player_facing = facing;
+            continue;
+        }
         if matches!(*player_state, PlayerState::Idle | PlayerState::Run) {
             if let Ok(ball_pos) = ball_query.get_single() {
                 *player_facing = if ball_pos.0.x - player_pos.0.x > 0. {
@@ -264,64 +314,129 @@ fn turn_player_toward_ball(
     }
 }
 
+/// Moves each player's body collider to wherever the tick left the player.
+fn sync_body_collider_system(
+    mut query: Query<(&WorldPosition, &mut ColliderPositionComponent), With<Player>>,
+) {
+    for (position, mut collider_position) in query.iter_mut() {
+        *collider_position = (position.0, Quat::IDENTITY).into();
+    }
+}
+
 fn player_spawn_system(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    setup: Res<MatchSetup>,
+    surface: Res<CourtSurface>,
+    difficulty: Res<CpuDifficulty>,
+    style: Res<CpuStyle>,
+    (asset_server, texture_atlases): (
+        Option<Res<AssetServer>>,
+        Option<ResMut<Assets<TextureAtlas>>>,
+    ),
     mut events: EventReader<SpawnPlayerEvent>,
 ) {
-    let player_texture_handle = asset_server.get_handle("textures/player.png");
-    let player_texture_atlas =
-        TextureAtlas::from_grid(player_texture_handle, Vec2::new(24.0, 24.0), 4, 8);
-    let player_texture_atlas_handle = texture_atlases.add(player_texture_atlas);
-
-    let opponent_texture_handle = asset_server.get_handle("textures/opponent.png");
-    let opponent_texture_atlas =
-        TextureAtlas::from_grid(opponent_texture_handle, Vec2::new(24.0, 24.0), 4, 8);
-    let opponent_texture_atlas_handle = texture_atlases.add(opponent_texture_atlas);
+    // there's nothing to draw with in headless mode
+    let atlases = asset_server
+        .zip(texture_atlases)
+        .map(|(asset_server, mut texture_atlases)| {
+            let player_texture_handle = asset_server.get_handle("textures/player.png");
+            let player_texture_atlas =
+                TextureAtlas::from_grid(player_texture_handle, Vec2::new(24.0, 24.0), 4, 8);
+            let opponent_texture_handle = asset_server.get_handle("textures/opponent.png");
+            let opponent_texture_atlas =
+                TextureAtlas::from_grid(opponent_texture_handle, Vec2::new(24.0, 24.0), 4, 8);
+            (
+                texture_atlases.add(player_texture_atlas),
+                texture_atlases.add(opponent_texture_atlas),
+            )
+        });
 
     for ev in events.iter() {
-        let speed = if ev.opponent {
-            PLAYER_SPEED * 0.5
-        } else {
-            PLAYER_SPEED
-        };
-        let texture_atlas_handle = if ev.opponent {
-            opponent_texture_atlas_handle.clone()
-        } else {
-            player_texture_atlas_handle.clone()
-        };
+        let speed = match ev.controller {
+            Controller::Keyboard(_) | Controller::Replay => PLAYER_SPEED,
+            Controller::Cpu => PLAYER_SPEED * difficulty.speed,
+        } * surface.traction;
         let id = commands
             .spawn_bundle((
-                if ev.opponent {
-                    Player::Opponent
-                } else {
-                    Player::User
-                },
+                ev.player,
+                PlayerSlot(ev.slot),
                 PlayerState::Idle,
                 PlayerSpeed(speed),
+                TopSpeed(speed),
                 PlayerFacing::Right,
+                ChargeTime::default(),
+                PlayerIntent::default(),
             ))
             .insert_bundle((
+                SceneEntity,
                 ev.position,
                 SyncWorldPosition,
                 WorldSprite {
                     base: Vec2::new(0.0, -10.5) * PX_SCALE,
-                    ..Default::default()
                 },
+                SpriteAnimation::player_idle(),
             ))
-            .insert_bundle(SpriteSheetBundle {
-                texture_atlas: texture_atlas_handle.clone(),
-                transform: Transform::from_scale(Vec3::splat(PX_SCALE)),
-                ..Default::default()
+            .insert_bundle(ColliderBundle {
+                collider_type: ColliderType::Sensor.into(),
+                shape: ColliderShape::capsule(
+                    Point::new(0.0, 0.0, PLAYER_BODY_RADIUS),
+                    Point::new(0.0, 0.0, PLAYER_BODY_HEIGHT - PLAYER_BODY_RADIUS),
+                    PLAYER_BODY_RADIUS,
+                )
+                .into(),
+                flags: ActiveEvents::INTERSECTION_EVENTS.into(),
+                position: (ev.position.0, Quat::IDENTITY).into(),
+                ..default()
             })
-            .insert(SpriteAnimation::player_idle())
             .id();
-        if ev.opponent {
-            commands.entity(id).insert(Opponent).insert(CpuControlled);
-        } else {
-            commands.entity(id).insert(UserControlled);
+        match ev.controller {
+            Controller::Keyboard(bindings) => commands
+                .entity(id)
+                .insert_bundle((UserControlled(bindings), ActionState::default())),
+            Controller::Replay => commands.entity(id).insert(ReplayControlled),
+            Controller::Cpu => commands.entity(id).insert_bundle((
+                CpuControlled,
+                CpuPlan {
+                    style: *style,
+                    at_net: false,
+                    intercept: None,
+                    facing: None,
+                    shot: None,
+                },
+            )),
+        };
+        if setup.doubles {
+            // slot 0 covers the deuce half and slot 1 the ad half, overlapping in the middle
+            let side = if ev.slot == 0 {
+                ServeSide::Deuce
+            } else {
+                ServeSide::Ad
+            };
+            let zone = if side_sign(ev.player, side) > 0. {
+                CoverZone {
+                    x_min: X_CENTER_LINE - 2.0,
+                    x_max: X_DOUBLES_LINE_RIGHT,
+                }
+            } else {
+                CoverZone {
+                    x_min: X_DOUBLES_LINE_LEFT,
+                    x_max: X_CENTER_LINE + 2.0,
+                }
+            };
+            commands.entity(id).insert(zone);
         }
+        let (player_atlas, opponent_atlas) = match &atlases {
+            Some(atlases) => atlases,
+            None => continue,
+        };
+        commands.entity(id).insert_bundle(SpriteSheetBundle {
+            texture_atlas: match ev.player {
+                Player::User => player_atlas.clone(),
+                Player::Opponent => opponent_atlas.clone(),
+            },
+            transform: Transform::from_scale(Vec3::splat(PX_SCALE)),
+            ..Default::default()
+        });
         commands
             .spawn_bundle((
                 Shadow {
@@ -333,14 +448,131 @@ fn player_spawn_system(
                     base: Vec2::new(0.0, -10.5) * PX_SCALE,
                 },
                 SyncWorldPosition,
+                SceneEntity,
             ))
             .insert_bundle(SpriteSheetBundle {
                 sprite: TextureAtlasSprite {
                     index: 15,
                     ..default()
                 },
-                texture_atlas: player_texture_atlas_handle.clone(),
+                texture_atlas: player_atlas.clone(),
                 ..default()
             });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestGame;
+
+    const POSITION: Vec3 = bevy::math::const_vec3!([0., -15., 0.]);
+
+    /// Charges for a moment, then releases.
+    fn swing(game: &mut TestGame, player: Entity) {
+        game.set_intent(
+            player,
+            PlayerIntent {
+                start_charge: true,
+                ..default()
+            },
+        );
+        game.advance(10);
+        game.set_intent(
+            player,
+            PlayerIntent {
+                release: true,
+                ..default()
+            },
+        );
+        game.advance(2);
+    }
+
+    /// Swings at a ball hanging `offset` from the sweet spot, and returns the hits.
+    fn swing_at(offset: Vec3) -> Vec<HitEvent> {
+        let mut game = TestGame::new();
+        game.without_gravity();
+        let player = game.spawn_player(Player::User, POSITION);
+        let ball = sweet_spot(POSITION, &PlayerFacing::Right) + offset;
+        game.spawn_ball(ball, Vec3::ZERO, Player::Opponent);
+        swing(&mut game, player);
+        game.hits
+    }
+
+    #[test]
+    fn hits_balls_within_reach_of_the_sweet_spot() {
+        let hits = swing_at(Vec3::ZERO);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].player, Player::User);
+        assert!(hits[0].new_velocity.y > 0., "hit toward the far court");
+
+        let edge = PLAYER_SWEET_SPOT_RADIUS * 0.9;
+        assert_eq!(swing_at(Vec3::new(0., edge, 0.)).len(), 1);
+        assert_eq!(swing_at(Vec3::new(0., 0., edge)).len(), 1);
+    }
+
+    #[test]
+    fn full_charge_lands_inside_the_baseline() {
+        let target = swing_target(Player::User, 1., 1.);
+        assert!(target.y > Y_FAR_MIDLINE && target.y < Y_FAR_BASELINE);
+        let target = swing_target(Player::Opponent, -1., 1.);
+        assert!(target.y < Y_NEAR_MIDLINE && target.y > Y_NEAR_BASELINE);
+    }
+
+    #[test]
+    fn misses_balls_out_of_reach() {
+        let beyond = PLAYER_SWEET_SPOT_RADIUS * 1.1;
+        assert!(swing_at(Vec3::new(0., beyond, 0.)).is_empty());
+        assert!(swing_at(Vec3::new(0., 0., beyond)).is_empty());
+    }
+
+    #[test]
+    fn cpu_reach_follows_difficulty() {
+        let beyond = Vec3::new(0., PLAYER_SWEET_SPOT_RADIUS * 1.5, 0.);
+        let mut game = TestGame::new();
+        game.without_gravity();
+        *game.resource_mut::<CpuDifficulty>() = CpuDifficulty::unfair();
+        let player = game.spawn_player(Player::User, POSITION);
+        game.insert(player, CpuControlled);
+        let ball = sweet_spot(POSITION, &PlayerFacing::Right) + beyond;
+        game.spawn_ball(ball, Vec3::ZERO, Player::Opponent);
+        swing(&mut game, player);
+        assert_eq!(game.hits.len(), 1);
+    }
+
+    #[test]
+    fn swing_cools_down_to_idle() {
+        let mut game = TestGame::new();
+        let player = game.spawn_player(Player::User, POSITION);
+        swing(&mut game, player);
+        let swinging = |game: &TestGame| {
+            matches!(
+                game.component::<PlayerState>(player),
+                Some(PlayerState::Swing)
+            )
+        };
+        assert!(swinging(&game));
+        assert!(game.component::<SwingCooldown>(player).is_some());
+
+        // a new charge can't start mid-swing
+        game.set_intent(
+            player,
+            PlayerIntent {
+                start_charge: true,
+                ..default()
+            },
+        );
+        let cooldown_ticks = (PLAYER_SWING_COOLDOWN_SECS / PHYSICS_STEP_SECS).ceil() as u32;
+        game.advance(cooldown_ticks / 2);
+        assert!(swinging(&game));
+
+        game.advance(cooldown_ticks);
+        let state = game.component::<PlayerState>(player);
+        assert!(
+            !matches!(state, Some(PlayerState::Charge)),
+            "the charge asked for mid-swing is dropped, not started once the swing ends"
+        );
+        assert!(matches!(state, Some(PlayerState::Idle)));
+        assert!(game.component::<SwingCooldown>(player).is_none());
+    }
+}
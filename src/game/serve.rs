@@ -0,0 +1,316 @@
+use super::ball::shot_velocity;
+use crate::*;
+
+pub(crate) struct ServePlugin;
+
+impl Plugin for ServePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .label(PointSystems)
+                .after(CallSystems)
+                .with_system(fault_system),
+        )
+        .add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .label(ServeSystems)
+                .label(ApplyIntentSystems)
+                .after(IntentSystems)
+                .with_system(hold_serve_positions_system.label(ServeStep::Hold))
+                .with_system(toss_system.label(ServeStep::Toss).after(ServeStep::Hold))
+                .with_system(serve_contact_system.after(ServeStep::Toss)),
+        );
+    }
+}
+
+/// Servers are put in place before they toss, and toss before the racket meets the ball.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+enum ServeStep {
+    Hold,
+    Toss,
+}
+
+/// Sign of the x coordinates of the deuce or ad half of `player`'s side of the court.
+pub(crate) fn side_sign(player: Player, side: ServeSide) -> f32 {
+    match (player, side) {
+        (Player::User, ServeSide::Deuce) | (Player::Opponent, ServeSide::Ad) => 1.0,
+        (Player::User, ServeSide::Ad) | (Player::Opponent, ServeSide::Deuce) => -1.0,
+    }
+}
+
+/// Which member of `player`'s team serves or receives the next point.
+pub(crate) fn active_slot(player: Player, score: &MatchScore, setup: &MatchSetup) -> u8 {
+    if !setup.doubles {
+        0
+    } else if player == score.server() {
+        score.server_slot()
+    } else {
+        // each receiver keeps to their own side of the court
+        match score.serve_side() {
+            ServeSide::Deuce => 0,
+            ServeSide::Ad => 1,
+        }
+    }
+}
+
+/// Where a player stands while waiting for the next serve. In doubles the server's partner
+/// waits at the net and the receiver's partner on the service line, both on the other half.
+pub(crate) fn serve_position(
+    player: Player,
+    slot: u8,
+    score: &MatchScore,
+    setup: &MatchSetup,
+) -> Vec3 {
+    let serving = player == score.server();
+    let sign = side_sign(player, score.serve_side());
+    let baseline = match player {
+        Player::User => Y_NEAR_BASELINE - 1.0,
+        Player::Opponent => Y_FAR_BASELINE + 1.0,
+    };
+    if slot == active_slot(player, score, setup) {
+        let x = if serving { 2.0 } else { 8.0 };
+        return Vec3::new(sign * x, baseline, 0.0);
+    }
+    let y = match (player, serving) {
+        (Player::User, true) => Y_NETLINE - 4.0,
+        (Player::Opponent, true) => Y_NETLINE + 4.0,
+        (Player::User, false) => Y_NEAR_MIDLINE,
+        (Player::Opponent, false) => Y_FAR_MIDLINE,
+    };
+    Vec3::new(-sign * 8.0, y, 0.0)
+}
+
+/// The corners of the service box diagonally opposite the server.
+pub(crate) fn service_box(score: &MatchScore) -> (Vec2, Vec2) {
+    let receiver = score.server().other();
+    let (x_min, x_max) = if side_sign(receiver, score.serve_side()) > 0. {
+        (X_CENTER_LINE, X_SINGLES_LINE_RIGHT)
+    } else {
+        (X_SINGLES_LINE_LEFT, X_CENTER_LINE)
+    };
+    let (y_min, y_max) = match receiver {
+        Player::User => (Y_NEAR_MIDLINE, Y_NETLINE),
+        Player::Opponent => (Y_NETLINE, Y_FAR_MIDLINE),
+    };
+    (Vec2::new(x_min, y_min), Vec2::new(x_max, y_max))
+}
+
+pub(crate) fn in_service_box(score: &MatchScore, position: Vec3) -> bool {
+    let (min, max) = service_box(score);
+    position.x >= min.x && position.x <= max.x && position.y >= min.y && position.y <= max.y
+}
+
+fn hold_serve_positions_system(
+    rally: Res<RallyState>,
+    score: Res<MatchScore>,
+    setup: Res<MatchSetup>,
+    mut query: Query<(&Player, &PlayerSlot, &mut PlayerState, &mut WorldPosition)>,
+) {
+    if rally.phase != RallyPhase::AwaitingServe {
+        return;
+    }
+    for (player, slot, mut state, mut position) in query.iter_mut() {
+        position.0 = serve_position(*player, slot.0, &score, &setup);
+        if matches!(*state, PlayerState::Run | PlayerState::Charge) {
+            *state = PlayerState::Idle;
+        }
+    }
+}
+
+fn toss_system(
+    mut commands: Commands,
+    score: Res<MatchScore>,
+    setup: Res<MatchSetup>,
+    mut rally: ResMut<RallyState>,
+    mut player_query: Query<(
+        Entity,
+        &Player,
+        &PlayerSlot,
+        &PlayerIntent,
+        &mut PlayerState,
+        &WorldPosition,
+    )>,
+    mut ball_events: EventWriter<SpawnBallEvent>,
+) {
+    if rally.phase != RallyPhase::AwaitingServe {
+        return;
+    }
+    for (entity, player, slot, intent, mut state, position) in player_query.iter_mut() {
+        if *player != score.server() || slot.0 != active_slot(*player, &score, &setup) {
+            continue;
+        }
+        if !intent.start_charge {
+            continue;
+        }
+        rally.phase = RallyPhase::Toss;
+        *state = PlayerState::Serve;
+        commands
+            .entity(entity)
+            .insert(ServeToss(Timer::from_seconds(SERVE_CONTACT_SECS, false)));
+        ball_events.send(SpawnBallEvent {
+            position: WorldPosition(position.0 + Vec3::Z * SERVE_TOSS_HEIGHT),
+            velocity: RigidBodyVelocity {
+                linvel: (Vec3::Z * SERVE_TOSS_SPEED).into(),
+                ..default()
+            },
+        });
+    }
+}
+
+fn serve_contact_system(
+    mut commands: Commands,
+    time: Res<GameTime>,
+    score: Res<MatchScore>,
+    mut rally: ResMut<RallyState>,
+    mut server_query: Query<(Entity, &Player, &mut ServeToss, &PlayerIntent)>,
+    ball_query: Query<(Entity, &WorldPosition), With<GameBall>>,
+    mut hit_events: EventWriter<HitEvent>,
+) {
+    for (entity, player, mut toss, intent) in server_query.iter_mut() {
+        if !toss.0.tick(time.delta()).just_finished() {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .remove::<ServeToss>()
+            .insert(SwingCooldown(Timer::from_seconds(
+                SERVE_COOLDOWN_SECS,
+                false,
+            )));
+        let (ball_id, ball_pos) = match ball_query.get_single() {
+            Ok(ball) => ball,
+            Err(_) => {
+                rally.phase = RallyPhase::AwaitingServe;
+                continue;
+            }
+        };
+        // fraction of the way across the box, from its left edge
+        let aim = 0.5 + 0.35 * intent.aim;
+        let (min, max) = service_box(&score);
+        let service_line = match player.other() {
+            Player::User => min.y,
+            Player::Opponent => max.y,
+        };
+        let target = Vec2::new(
+            min.x + aim * (max.x - min.x),
+            Y_NETLINE + 0.8 * (service_line - Y_NETLINE),
+        );
+        hit_events.send(HitEvent {
+            ball_id,
+            player: *player,
+            new_velocity: shot_velocity(ball_pos.0, target, SERVE_SPEED),
+        });
+        rally.phase = RallyPhase::Serve;
+    }
+}
+
+fn fault_system(
+    mut commands: Commands,
+    mut faults: EventReader<FaultEvent>,
+    mut lets: EventReader<LetEvent>,
+    score: Res<MatchScore>,
+    mut rally: ResMut<RallyState>,
+    ball_query: Query<Entity, BallOrShadow>,
+    mut point_over_events: EventWriter<PointOverEvent>,
+) {
+    let mut replay_serve = false;
+    for _ in lets.iter() {
+        info!("let");
+        replay_serve = true;
+    }
+    for ev in faults.iter() {
+        match rally.attempt {
+            ServeAttempt::First => {
+                info!("fault");
+                rally.attempt = ServeAttempt::Second;
+                replay_serve = true;
+            }
+            ServeAttempt::Second => {
+                info!("double fault");
+                point_over_events.send(PointOverEvent {
+                    winner: score.server().other(),
+                    reason: PointReason::DoubleFault,
+                    landing: ev.landing,
+                    last_hitter: score.server(),
+                });
+            }
+        }
+    }
+    if replay_serve {
+        for id in ball_query.iter() {
+            commands.entity(id).despawn();
+        }
+        rally.phase = RallyPhase::AwaitingServe;
+        rally.shots = 0;
+        rally.landing = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestGame;
+
+    /// Long of the service box the near player serves into from the deuce court.
+    const LONG: Vec3 = bevy::math::const_vec3!([-4., Y_FAR_MIDLINE + 3., 3.]);
+    /// Ticks for a serve dropped anywhere here to land.
+    const LANDING_TICKS: u32 = 90;
+
+    /// Puts a serve by the near player in the air at `position`, dropping straight down.
+    fn serve_ball(game: &mut TestGame, position: Vec3) {
+        let mut rally = game.resource_mut::<RallyState>();
+        rally.phase = RallyPhase::Serve;
+        rally.shots = 1;
+        game.spawn_ball(position, Vec3::ZERO, Player::User);
+        game.advance(LANDING_TICKS);
+    }
+
+    #[test]
+    fn fault_gives_the_server_a_second_serve() {
+        let mut game = TestGame::new();
+        game.await_serve(Player::User);
+        let server = game.spawn_player(Player::User, Vec3::ZERO);
+        serve_ball(&mut game, LONG);
+        assert!(game.points.is_empty());
+        let rally = game.resource_mut::<RallyState>();
+        assert_eq!(rally.phase, RallyPhase::AwaitingServe);
+        assert_eq!(rally.attempt, ServeAttempt::Second);
+
+        game.set_intent(
+            server,
+            PlayerIntent {
+                start_charge: true,
+                ..default()
+            },
+        );
+        game.advance(2 * 60);
+        assert_eq!(game.hits.len(), 1);
+        assert_eq!(game.hits[0].player, Player::User);
+    }
+
+    #[test]
+    fn net_cord_serve_landing_in_the_box_is_replayed() {
+        let mut game = TestGame::new();
+        game.await_serve(Player::User);
+        // clips the top of the net and drops into the box beyond it
+        serve_ball(&mut game, Vec3::new(-4., Y_NETLINE + 0.9, 5.));
+        assert!(game.resource_mut::<BallTouchedNet>().0);
+        assert!(game.points.is_empty());
+        let rally = game.resource_mut::<RallyState>();
+        assert_eq!(rally.phase, RallyPhase::AwaitingServe);
+        assert_eq!(rally.attempt, ServeAttempt::First);
+    }
+
+    #[test]
+    fn two_faults_lose_the_server_the_point() {
+        let mut game = TestGame::new();
+        game.await_serve(Player::User);
+        serve_ball(&mut game, LONG);
+        assert!(game.points.is_empty());
+        serve_ball(&mut game, LONG);
+        let point = game.points.first().map(|ev| (ev.winner, ev.reason));
+        assert_eq!(point, Some((Player::Opponent, PointReason::DoubleFault)));
+    }
+}
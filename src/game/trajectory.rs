@@ -0,0 +1,237 @@
+//! Predicts the ball's flight so the CPU can move before the ball gets there.
+//!
+//! The flight is stepped forward `STEP_SECS` at a time under gravity, bouncing as the court
+//! surface would have it, so predictions stay close to what actually happens without running
+//! the physics world.
+
+use crate::*;
+
+const STEP_SECS: f32 = 1. / 120.;
+const MAX_SECS: f32 = 5.;
+
+/// What the ball's flight depends on besides its own position and velocity.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BallPhysics {
+    pub(crate) gravity: f32,
+    pub(crate) restitution: f32,
+    /// Fraction of horizontal speed kept through a bounce.
+    pub(crate) speed_retention: f32,
+}
+
+impl BallPhysics {
+    pub(crate) fn new(rapier: &RapierConfiguration, surface: &CourtSurface) -> Self {
+        Self {
+            gravity: -rapier.gravity.z,
+            // rapier averages the two colliders' restitution by default
+            restitution: (BALL_RESTITUTION + surface.restitution) / 2.,
+            speed_retention: surface.speed_retention,
+        }
+    }
+}
+
+/// Where a player can reach the ball: a band of court lengthwise and a greatest height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct HittingZone {
+    pub(crate) y_min: f32,
+    pub(crate) y_max: f32,
+    pub(crate) z_max: f32,
+    /// Whether the ball may be taken before it bounces.
+    pub(crate) volley: bool,
+}
+
+impl HittingZone {
+    /// From a few steps off the net to well behind the baseline on `player`'s side, or up to
+    /// the wall behind the far court, since a ball that bounced and reaches it can't be played.
+    pub(crate) fn for_player(player: Player) -> Self {
+        let (y_min, y_max) = match player {
+            Player::User => (Y_NEAR_BASELINE - 6., Y_NETLINE - 3.),
+            Player::Opponent => (Y_NETLINE + 3., Y_FAR_WALL - BALL_RADIUS),
+        };
+        Self {
+            y_min,
+            y_max,
+            z_max: 3.,
+            volley: false,
+        }
+    }
+
+    /// Between the net and the service line on `player`'s side, taking the ball in the air.
+    pub(crate) fn at_net(player: Player) -> Self {
+        let (y_min, y_max) = match player {
+            Player::User => (Y_NEAR_MIDLINE, Y_NETLINE - 2.),
+            Player::Opponent => (Y_NETLINE + 2., Y_FAR_MIDLINE),
+        };
+        Self {
+            y_min,
+            y_max,
+            z_max: 4.,
+            volley: true,
+        }
+    }
+
+    fn contains_y(&self, y: f32) -> bool {
+        (self.y_min..=self.y_max).contains(&y)
+    }
+}
+
+/// A point on the ball's predicted path, `secs` from now.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PathPoint {
+    pub(crate) position: Vec3,
+    pub(crate) secs: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Prediction {
+    /// Where the ball next touches the floor.
+    pub(crate) landing: Option<PathPoint>,
+    /// Where the ball can best be hit in the zone: at the top of the arc after its bounce, or
+    /// once it has dropped below the zone's height, or just before it leaves the zone. Volleys
+    /// are taken as soon as the ball is low enough.
+    pub(crate) intercept: Option<PathPoint>,
+}
+
+/// Follows the ball until it's intercepted, bounces twice or is stopped by the net or the far
+/// wall. A ball that has `bounced` already can be intercepted straight away and has no landing
+/// ahead.
+pub(crate) fn predict(
+    mut position: Vec3,
+    mut velocity: Vec3,
+    mut bounced: bool,
+    physics: &BallPhysics,
+    zone: &HittingZone,
+) -> Prediction {
+    let mut prediction = Prediction::default();
+    let mut secs = 0.;
+    while secs < MAX_SECS {
+        let previous = position;
+        position += velocity * STEP_SECS - Vec3::Z * 0.5 * physics.gravity * STEP_SECS.powi(2);
+        velocity.z -= physics.gravity * STEP_SECS;
+        secs += STEP_SECS;
+
+        let crossed_net = (previous.y - Y_NETLINE).signum() != (position.y - Y_NETLINE).signum();
+        if crossed_net && position.z < NET_HEIGHT + BALL_RADIUS {
+            return prediction;
+        }
+        if position.y > Y_FAR_WALL - BALL_RADIUS {
+            return prediction;
+        }
+        if position.z < BALL_RADIUS && velocity.z < 0. {
+            if bounced {
+                return prediction;
+            }
+            bounced = true;
+            position.z = BALL_RADIUS;
+            velocity.z *= -physics.restitution;
+            velocity.x *= physics.speed_retention;
+            velocity.y *= physics.speed_retention;
+            prediction.landing = Some(PathPoint { position, secs });
+            continue;
+        }
+        if !(bounced || zone.volley) || !zone.contains_y(position.y) {
+            continue;
+        }
+        let next_y = position.y + velocity.y * STEP_SECS;
+        let reachable = position.z <= zone.z_max;
+        let at_apex = velocity.z <= 0. && reachable;
+        if (zone.volley && reachable) || at_apex || !zone.contains_y(next_y) {
+            prediction.intercept = Some(PathPoint { position, secs });
+            return prediction;
+        }
+    }
+    prediction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::ball::shot_velocity;
+    use super::*;
+
+    const PHYSICS: BallPhysics = BallPhysics {
+        gravity: GRAVITY,
+        restitution: 0.6,
+        speed_retention: 0.85,
+    };
+
+    #[test]
+    fn lands_where_the_shot_was_aimed() {
+        let from = Vec3::new(0., Y_NEAR_BASELINE, 2.);
+        let target = Vec2::new(6., Y_FAR_MIDLINE + 5.);
+        let velocity = shot_velocity(from, target, 20.);
+        let zone = HittingZone::for_player(Player::Opponent);
+        let prediction = predict(from, velocity, false, &PHYSICS, &zone);
+        let landing = prediction.landing.unwrap();
+        assert!((landing.position.truncate() - target).length() < 0.5);
+
+        let intercept = prediction.intercept.unwrap();
+        assert!(intercept.secs > landing.secs);
+        assert!(intercept.position.y > landing.position.y);
+        assert!(zone.contains_y(intercept.position.y));
+    }
+
+    #[test]
+    fn ball_into_the_net_is_never_intercepted() {
+        let from = Vec3::new(0., Y_NETLINE - 3., 2.);
+        let velocity = Vec3::new(0., 25., 2.);
+        let prediction = predict(
+            from,
+            velocity,
+            false,
+            &PHYSICS,
+            &HittingZone::for_player(Player::Opponent),
+        );
+        assert_eq!(prediction, Prediction::default());
+    }
+
+    #[test]
+    fn ball_going_away_is_never_intercepted() {
+        let from = Vec3::new(0., Y_FAR_BASELINE, 2.);
+        let velocity = shot_velocity(from, Vec2::new(0., Y_NEAR_MIDLINE - 4.), 20.);
+        let prediction = predict(
+            from,
+            velocity,
+            false,
+            &PHYSICS,
+            &HittingZone::for_player(Player::Opponent),
+        );
+        assert!(prediction.landing.is_some());
+        assert_eq!(prediction.intercept, None);
+    }
+
+    #[test]
+    fn volleys_are_taken_before_the_bounce() {
+        let from = Vec3::new(0., Y_NEAR_BASELINE, 2.);
+        let velocity = shot_velocity(from, Vec2::new(0., Y_FAR_BASELINE - 2.), 20.);
+        let zone = HittingZone::at_net(Player::Opponent);
+        let prediction = predict(from, velocity, false, &PHYSICS, &zone);
+        let intercept = prediction.intercept.unwrap();
+        assert!(zone.contains_y(intercept.position.y));
+        assert!(
+            intercept.secs
+                < prediction
+                    .landing
+                    .map_or(f32::INFINITY, |landing| landing.secs)
+        );
+    }
+
+    #[test]
+    fn deep_ball_is_taken_in_front_of_the_far_wall() {
+        let from = Vec3::new(0., Y_NEAR_BASELINE, 2.);
+        let velocity = shot_velocity(from, Vec2::new(4., Y_FAR_BASELINE - 1.), 26.);
+        let zone = HittingZone::for_player(Player::Opponent);
+        let prediction = predict(from, velocity, false, &PHYSICS, &zone);
+        let intercept = prediction.intercept.unwrap();
+        assert!(intercept.position.y <= Y_FAR_WALL - BALL_RADIUS);
+        assert!(intercept.secs > prediction.landing.unwrap().secs);
+    }
+
+    #[test]
+    fn ball_that_bounced_is_intercepted_before_landing_again() {
+        let from = Vec3::new(-3., Y_FAR_MIDLINE + 2., BALL_RADIUS);
+        let velocity = Vec3::new(-1., 12., 7.);
+        let zone = HittingZone::for_player(Player::Opponent);
+        let prediction = predict(from, velocity, true, &PHYSICS, &zone);
+        assert_eq!(prediction.landing, None);
+        assert!(prediction.intercept.unwrap().position.y > from.y);
+    }
+}
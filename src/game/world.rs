@@ -1,18 +1,91 @@
 use crate::*;
+use bevy_rapier3d::physics::{
+    self, JointsEntityMap, ModificationTracker, PhysicsSystems, SimulationToRenderTime,
+};
 
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(sync_physics_coords).add_system_set(
-            SystemSet::on_update(AppState::InGame)
-                .with_system(sync_transforms)
-                .with_system(sync_shadow_position_system)
-                .with_system(sync_physics_coords),
-        );
+        app.insert_resource(PhysicsSchedule::new())
+            .insert_resource(PhysicsPipeline::new())
+            .insert_resource(QueryPipeline::new())
+            .insert_resource(RapierConfiguration::default())
+            .insert_resource(IntegrationParameters::default())
+            .insert_resource(BroadPhase::new())
+            .insert_resource(NarrowPhase::new())
+            .insert_resource(IslandManager::new())
+            .insert_resource(ImpulseJointSet::new())
+            .insert_resource(MultibodyJointSet::new())
+            .insert_resource(CCDSolver::new())
+            .insert_resource(SimulationToRenderTime::default())
+            .insert_resource(JointsEntityMap::default())
+            .insert_resource(ModificationTracker::default())
+            .insert_resource(PhysicsHooksWithQueryObject::<NoUserData>(Box::new(())))
+            .add_event::<ContactEvent>()
+            .add_event::<IntersectionEvent>()
+            .add_system_to_stage(
+                FixedUpdate,
+                store_previous_positions_system
+                    .exclusive_system()
+                    .at_start(),
+            )
+            .add_system_to_stage(FixedUpdate, step_physics_system.exclusive_system().at_end())
+            // bodies and colliders despawned after a frame's last tick
+            .add_system_to_stage(CoreStage::PostUpdate, physics::collect_removals)
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                in_state(AppState::InGame)
+                    .before(bevy::transform::TransformSystem::TransformPropagate)
+                    .with_system(sync_transforms)
+                    .with_system(sync_shadow_position_system),
+            );
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash, StageLabel)]
+enum PhysicsStage {
+    Attach,
+    Step,
+}
+
+/// Rapier's systems, run at the end of every tick so that each tick is exactly one physics step,
+/// however many ticks a frame runs. `RapierPhysicsPlugin` would step once a frame instead.
+struct PhysicsSchedule(Schedule);
+
+impl PhysicsSchedule {
+    fn new() -> Self {
+        let mut schedule = Schedule::default();
+        schedule
+            .add_stage(
+                PhysicsStage::Attach,
+                SystemStage::single_threaded()
+                    .with_system(physics::attach_bodies_and_colliders_system),
+            )
+            .add_stage(
+                PhysicsStage::Step,
+                SystemStage::single_threaded()
+                    .with_system(
+                        physics::finalize_collider_attach_to_bodies
+                            .label(PhysicsSystems::FinalizeColliderAttachToBodies),
+                    )
+                    .with_system(
+                        physics::step_world_system::<NoUserData>
+                            .label(PhysicsSystems::StepWorld)
+                            .after(PhysicsSystems::FinalizeColliderAttachToBodies),
+                    )
+                    .with_system(sync_physics_coords.after(PhysicsSystems::StepWorld)),
+            );
+        Self(schedule)
+    }
+}
+
+fn step_physics_system(world: &mut World) {
+    world.resource_scope(|world, mut schedule: Mut<PhysicsSchedule>| {
+        schedule.0.run_once(world);
+    });
+}
+
 const DEPTH_SCALE: f32 = 0.0055;
 
 fn sync_physics_coords(mut query: Query<(&mut WorldPosition, &RigidBodyPositionComponent)>) {
@@ -21,33 +94,55 @@ fn sync_physics_coords(mut query: Query<(&mut WorldPosition, &RigidBodyPositionC
     }
 }
 
-fn sync_transforms(
-    mut query: Query<
-        (
-            &mut Transform,
-            &WorldPosition,
-            &WorldSprite,
-            Option<&Shadow>,
-        ),
-        With<SyncWorldPosition>,
-    >,
-) {
-    for (mut transform, world_coords, world_sprite, maybe_scale) in query.iter_mut() {
+/// Remembers where everything was before this tick moves it.
+fn store_previous_positions_system(world: &mut World) {
+    let mut query = world.query_filtered::<(
+        Entity,
+        &WorldPosition,
+        Option<&mut PreviousWorldPosition>,
+    ), With<SyncWorldPosition>>();
+    let mut new = Vec::new();
+    for (entity, position, previous) in query.iter_mut(world) {
+        match previous {
+            Some(mut previous) => previous.0 = position.0,
+            None => new.push((entity, PreviousWorldPosition(position.0))),
+        }
+    }
+    for (entity, previous) in new {
+        world.entity_mut(entity).insert(previous);
+    }
+}
+
+/// What `sync_transforms` reads to place each sprite, and the transform it sets.
+type SyncedSprite<'a> = (
+    &'a mut Transform,
+    &'a WorldPosition,
+    Option<&'a PreviousWorldPosition>,
+    &'a WorldSprite,
+    Option<&'a Shadow>,
+);
+
+fn sync_transforms(time: Res<GameTime>, mut query: Query<SyncedSprite, With<SyncWorldPosition>>) {
+    let interpolation = time.interpolation();
+    for (mut transform, world_coords, previous, world_sprite, maybe_scale) in query.iter_mut() {
+        let position = match previous {
+            Some(previous) => previous.0.lerp(world_coords.0, interpolation),
+            None => world_coords.0,
+        };
         let shadow_scale = maybe_scale.map(|shadow| shadow.scale).unwrap_or(1.0);
-        let depth_scale = 1.0 - DEPTH_SCALE * world_coords.0.y;
-        let scaled = world_coords.0 * WORLD_SCALE * depth_scale;
+        let depth_scale = 1.0 - DEPTH_SCALE * position.y;
+        let scaled = position * WORLD_SCALE * depth_scale;
         transform.translation =
             (Vec2::new(scaled.x, scaled.y + scaled.z) - world_sprite.base).extend(depth_scale);
-        // transform.scale = Vec3::splat(PX_SCALE * depth_scale * world_sprite.custom_scale);
         transform.scale = Vec3::splat(PX_SCALE * depth_scale * shadow_scale);
     }
 }
 
 fn sync_shadow_position_system(
-    mut shadow_query: Query<(&mut Shadow, &mut WorldPosition, &mut WorldSprite)>,
+    mut shadow_query: Query<(&mut Shadow, &mut WorldPosition)>,
     parent_query: Query<&WorldPosition, Without<Shadow>>,
 ) {
-    for (mut shadow, mut shadow_position, mut sprite) in shadow_query.iter_mut() {
+    for (mut shadow, mut shadow_position) in shadow_query.iter_mut() {
         if let Ok(parent_position) = parent_query.get(shadow.parent) {
             *shadow_position = *parent_position;
             shadow_position.0.y += 0.01;
@@ -0,0 +1,403 @@
+//! Maps keys to player actions.
+//!
+//! Each keyboard player has a binding set, read from `BINDINGS_PATH` at startup. Unless running
+//! headless or simulating, the file is written with the defaults if it doesn't exist yet, so
+//! players can edit it. The format is one
+//! `[playerN]` section per binding set followed by `action = Key, Key` lines.
+//!
+//! Keyboard players' intents are filled in from their actions here, before any game system
+//! runs.
+//!
+//! Gamepads are assigned to players in the order they connect: keyboard player N also reads the
+//! Nth gamepad, whose buttons are fixed. The sticks give analog movement and aim on top of the
+//! digital actions. Gamepads are only read when built with the `gamepad` feature, on by default.
+
+use crate::*;
+use bevy::input::InputSystem;
+use std::io::ErrorKind;
+
+const BINDINGS_PATH: &str = "bindings.cfg";
+
+/// Stick deflection below which input is ignored, as a fraction of full travel.
+const GAMEPAD_DEADZONE: f32 = 0.2;
+
+const ACTION_NAMES: [(Action, &str); 8] = [
+    (Action::MoveUp, "move_up"),
+    (Action::MoveDown, "move_down"),
+    (Action::MoveLeft, "move_left"),
+    (Action::MoveRight, "move_right"),
+    (Action::Swing, "swing"),
+    (Action::AimLeft, "aim_left"),
+    (Action::AimRight, "aim_right"),
+    (Action::Pause, "pause"),
+];
+
+pub(crate) struct InputPlugin {
+    /// Writes the default bindings file if there isn't one.
+    pub(crate) save_defaults: bool,
+}
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_bindings(BINDINGS_PATH, self.save_defaults))
+            .init_resource::<GamepadSlots>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                gamepad_connection_system.after(InputSystem),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_action_state_system.after(InputSystem),
+            );
+    }
+}
+
+impl Action {
+    fn from_name(name: &str) -> Option<Self> {
+        ACTION_NAMES
+            .iter()
+            .find(|(_, action_name)| *action_name == name)
+            .map(|(action, _)| *action)
+    }
+}
+
+impl ActionBindings {
+    pub(crate) fn keys(&self, action: Action) -> &[KeyCode] {
+        self.0
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    pub(crate) fn bind(&mut self, action: Action, key: KeyCode) {
+        match self.0.iter_mut().find(|(bound, _)| *bound == action) {
+            Some((_, keys)) if !keys.contains(&key) => keys.push(key),
+            Some(_) => {}
+            None => self.0.push((action, vec![key])),
+        }
+    }
+
+    fn unbind(&mut self, action: Action) {
+        self.0.retain(|(bound, _)| *bound != action);
+    }
+
+    fn from_pairs(pairs: &[(Action, KeyCode)]) -> Self {
+        let mut bindings = Self::default();
+        for &(action, key) in pairs {
+            bindings.bind(action, key);
+        }
+        bindings
+    }
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            players: vec![
+                ActionBindings::from_pairs(&[
+                    (Action::MoveUp, KeyCode::Up),
+                    (Action::MoveDown, KeyCode::Down),
+                    (Action::MoveLeft, KeyCode::Left),
+                    (Action::MoveRight, KeyCode::Right),
+                    (Action::Swing, KeyCode::Space),
+                    (Action::AimLeft, KeyCode::Left),
+                    (Action::AimRight, KeyCode::Right),
+                    (Action::Pause, KeyCode::Escape),
+                ]),
+                ActionBindings::from_pairs(&[
+                    (Action::MoveUp, KeyCode::W),
+                    (Action::MoveDown, KeyCode::S),
+                    (Action::MoveLeft, KeyCode::A),
+                    (Action::MoveRight, KeyCode::D),
+                    (Action::Swing, KeyCode::F),
+                    (Action::AimLeft, KeyCode::A),
+                    (Action::AimRight, KeyCode::D),
+                    (Action::Pause, KeyCode::Escape),
+                ]),
+            ],
+        }
+    }
+}
+
+impl InputBindings {
+    /// The binding set for keyboard player `index`, falling back to the first set.
+    pub(crate) fn player(&self, index: usize) -> &ActionBindings {
+        &self.players[index.min(self.players.len() - 1)]
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let defaults = Self::default().players;
+        let mut players: Vec<ActionBindings> = Vec::new();
+        for (number, line) in text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+        {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                match section.strip_prefix("player").map(str::parse::<usize>) {
+                    // each listed action replaces the keys this slot has by default
+                    Some(Ok(n)) if n == players.len() + 1 => {
+                        players.push(defaults.get(players.len()).cloned().unwrap_or_default())
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {number}: expected [player{}]",
+                            players.len() + 1
+                        ))
+                    }
+                }
+                continue;
+            }
+            let bindings = players
+                .last_mut()
+                .ok_or_else(|| format!("line {number}: binding outside a [playerN] section"))?;
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {number}: expected `action = Key, Key`"))?;
+            let action = Action::from_name(name.trim())
+                .ok_or_else(|| format!("line {number}: unknown action {:?}", name.trim()))?;
+            bindings.unbind(action);
+            for key in keys.split(',').map(str::trim).filter(|key| !key.is_empty()) {
+                let key = key_from_name(key)
+                    .ok_or_else(|| format!("line {number}: unknown key {key:?}"))?;
+                bindings.bind(action, key);
+            }
+        }
+        // sets missing from the file keep their defaults
+        if players.len() < defaults.len() {
+            players.extend_from_slice(&defaults[players.len()..]);
+        }
+        Ok(Self { players })
+    }
+
+    fn to_config(&self) -> String {
+        let mut text = String::from(
+            "# Key bindings for each keyboard player. List several keys with commas.\n",
+        );
+        for (i, bindings) in self.players.iter().enumerate() {
+            text += &format!("\n[player{}]\n", i + 1);
+            for (action, name) in ACTION_NAMES {
+                let keys: Vec<_> = bindings
+                    .keys(action)
+                    .iter()
+                    .map(|key| format!("{key:?}"))
+                    .collect();
+                text += &format!("{name} = {}\n", keys.join(", "));
+            }
+        }
+        text
+    }
+}
+
+fn load_bindings(path: &str, save_defaults: bool) -> InputBindings {
+    match std::fs::read_to_string(path) {
+        Ok(text) => InputBindings::parse(&text).unwrap_or_else(|err| {
+            warn!("ignoring {path}: {err}");
+            default()
+        }),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let bindings = InputBindings::default();
+            if save_defaults {
+                save_bindings(path, &bindings);
+            }
+            bindings
+        }
+        Err(err) => {
+            warn!("couldn't read {path}: {err}");
+            default()
+        }
+    }
+}
+
+fn save_bindings(path: &str, bindings: &InputBindings) {
+    if let Err(err) = std::fs::write(path, bindings.to_config()) {
+        warn!("couldn't write {path}: {err}");
+    }
+}
+
+fn gamepad_buttons(action: Action) -> &'static [GamepadButtonType] {
+    use GamepadButtonType::*;
+    match action {
+        Action::MoveUp => &[DPadUp],
+        Action::MoveDown => &[DPadDown],
+        Action::MoveLeft | Action::AimLeft => &[DPadLeft],
+        Action::MoveRight | Action::AimRight => &[DPadRight],
+        Action::Swing => &[South, RightTrigger, RightTrigger2],
+        Action::Pause => &[Start],
+    }
+}
+
+/// Radial deadzone, rescaled so the stick still reaches full deflection at its edge.
+fn apply_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length < GAMEPAD_DEADZONE {
+        Vec2::ZERO
+    } else {
+        stick / length * ((length - GAMEPAD_DEADZONE) / (1. - GAMEPAD_DEADZONE)).min(1.)
+    }
+}
+
+/// Keeps each gamepad in the slot it first connected to, so unplugging one player's pad
+/// doesn't hand the other player's pad to them.
+fn gamepad_connection_system(
+    mut events: EventReader<GamepadEvent>,
+    mut slots: ResMut<GamepadSlots>,
+) {
+    for GamepadEvent(gamepad, event) in events.iter() {
+        match event {
+            GamepadEventType::Connected => {
+                let slot = match slots.0.iter().position(Option::is_none) {
+                    Some(slot) => slot,
+                    None => {
+                        slots.0.push(None);
+                        slots.0.len() - 1
+                    }
+                };
+                slots.0[slot] = Some(*gamepad);
+                info!("gamepad {} connected for player {}", gamepad.0, slot + 1);
+            }
+            GamepadEventType::Disconnected => {
+                for slot in slots.0.iter_mut().filter(|slot| **slot == Some(*gamepad)) {
+                    *slot = None;
+                }
+                info!("gamepad {} disconnected", gamepad.0);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn update_action_state_system(
+    keyboard: Res<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    bindings: Res<InputBindings>,
+    slots: Res<GamepadSlots>,
+    mut query: Query<(&UserControlled, &mut ActionState, &mut PlayerIntent)>,
+) {
+    for (user, mut state, mut intent) in query.iter_mut() {
+        let keys = bindings.player(user.0);
+        let gamepad = slots.0.get(user.0).copied().flatten();
+        let stick = |x, y| {
+            gamepad.map_or(Vec2::ZERO, |gamepad| {
+                let axis = |axis_type| axes.get(GamepadAxis(gamepad, axis_type)).unwrap_or(0.);
+                apply_deadzone(Vec2::new(axis(x), axis(y)))
+            })
+        };
+        let left_stick = stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+        let right_stick = stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY);
+
+        let pressed: HashSet<Action> = ACTION_NAMES
+            .iter()
+            .map(|(action, _)| *action)
+            .filter(|action| {
+                keys.keys(*action).iter().any(|key| keyboard.pressed(*key))
+                    || gamepad.is_some_and(|gamepad| {
+                        gamepad_buttons(*action)
+                            .iter()
+                            .any(|button| buttons.pressed(GamepadButton(gamepad, *button)))
+                    })
+            })
+            .collect();
+        let digital = |negative: Action, positive: Action| {
+            pressed.contains(&positive) as i32 as f32 - pressed.contains(&negative) as i32 as f32
+        };
+        let movement = if left_stick != Vec2::ZERO {
+            left_stick
+        } else {
+            Vec2::new(
+                digital(Action::MoveLeft, Action::MoveRight),
+                digital(Action::MoveDown, Action::MoveUp),
+            )
+            .normalize_or_zero()
+        };
+        // aim with the right stick, or with the left one like the arrow keys
+        let aim = if right_stick.x != 0. {
+            right_stick.x
+        } else if left_stick.x != 0. {
+            left_stick.x
+        } else {
+            digital(Action::AimLeft, Action::AimRight)
+        };
+
+        state.previous = std::mem::replace(&mut state.pressed, pressed);
+
+        // presses are kept until a tick acts on them
+        intent.movement = movement;
+        intent.aim = aim;
+        intent.start_charge |= state.just_pressed(Action::Swing);
+        intent.release |= state.just_released(Action::Swing);
+    }
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        /// Parses the keys that can appear in the config file, spelled like `KeyCode`'s variants.
+        fn key_from_name(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($key) => Some(KeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K, L,
+    M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11,
+    F12, Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space,
+    Tab, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    LAlt, LBracket, LControl, LShift, RAlt, RBracket, RControl, RShift, Apostrophe, Backslash,
+    Comma, Equals, Grave, Minus, Period, Semicolon, Slash,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_round_trips() {
+        let bindings = InputBindings::default();
+        assert_eq!(InputBindings::parse(&bindings.to_config()), Ok(bindings));
+    }
+
+    #[test]
+    fn multiple_keys_per_action() {
+        let bindings = InputBindings::parse("[player1]\nswing = Space, Return\n").unwrap();
+        assert_eq!(
+            bindings.player(0).keys(Action::Swing),
+            &[KeyCode::Space, KeyCode::Return]
+        );
+        // actions the section doesn't list keep the slot's default keys
+        assert_eq!(
+            bindings.player(0).keys(Action::MoveUp),
+            InputBindings::default().player(0).keys(Action::MoveUp)
+        );
+        // the second set wasn't in the file
+        assert_eq!(bindings.player(1), InputBindings::default().player(1));
+    }
+
+    #[test]
+    fn listed_action_replaces_default_keys() {
+        let bindings = InputBindings::parse("[player1]\n[player2]\nswing = G\n").unwrap();
+        assert_eq!(bindings.player(1).keys(Action::Swing), &[KeyCode::G]);
+        assert_eq!(bindings.player(0), InputBindings::default().player(0));
+    }
+
+    #[test]
+    fn deadzone_rescales_stick() {
+        assert_eq!(apply_deadzone(Vec2::new(0.1, 0.1)), Vec2::ZERO);
+        assert_eq!(apply_deadzone(Vec2::new(0., -1.)), Vec2::new(0., -1.));
+        assert!((apply_deadzone(Vec2::new(0.6, 0.)).x - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!(InputBindings::parse("[player1]\njump = Space\n").is_err());
+        assert!(InputBindings::parse("[player1]\nswing = Spacebar\n").is_err());
+        assert!(InputBindings::parse("swing = Space\n").is_err());
+    }
+}
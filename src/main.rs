@@ -1,15 +1,32 @@
 #![feature(try_blocks)]
 
-use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use bevy::diagnostic::LogDiagnosticsPlugin;
+use bevy::ecs::schedule::ShouldRun;
 use bevy::prelude::*;
+use bevy::utils::HashSet;
 use bevy_easings::*;
 use bevy_rapier3d::prelude::*;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
 use std::time::Duration;
 
 mod game;
+mod input;
+mod menu;
+mod options;
+mod replay;
+mod scoring;
 mod setup;
+mod simulation;
+#[cfg(test)]
+mod testing;
 mod ui;
 
+use game::{CourtSurface, CpuDifficulty, CpuStyle, PathPoint};
+use scoring::*;
+
+/// The face of the wall behind the far court.
+const Y_FAR_WALL: f32 = 14.;
 const Y_FAR_BASELINE: f32 = 10.5;
 const Y_FAR_MIDLINE: f32 = 0.5;
 const Y_NETLINE: f32 = -7.;
@@ -25,15 +42,38 @@ const X_DOUBLES_LINE_RIGHT: f32 = 18.75;
 const NET_HEIGHT: f32 = 2.5;
 const NET_THICKNESS: f32 = 0.05;
 
-const KEY_CODE_UP: KeyCode = KeyCode::Up;
-const KEY_CODE_DOWN: KeyCode = KeyCode::Down;
-const KEY_CODE_LEFT: KeyCode = KeyCode::Left;
-const KEY_CODE_RIGHT: KeyCode = KeyCode::Right;
-const KEY_CODE_ACTION: KeyCode = KeyCode::Space;
+const GRAVITY: f32 = 15.;
+const BALL_RADIUS: f32 = 1.;
+const BALL_RESTITUTION: f32 = 0.6;
 
 const PLAYER_SPEED: f32 = 15.;
 const PLAYER_CHARGING_SPEED_FACTOR: f32 = 0.4;
 const PLAYER_SWING_COOLDOWN_SECS: f32 = 0.5;
+const PLAYER_FULL_CHARGE_SECS: f32 = 1.;
+const PLAYER_SWEET_SPOT_RADIUS: f32 = 2.;
+/// A player's body, which the ball can touch, is a capsule standing on the court.
+const PLAYER_BODY_RADIUS: f32 = 0.4;
+const PLAYER_BODY_HEIGHT: f32 = 4.;
+const SHOT_SPEED_MIN: f32 = 14.;
+const SHOT_SPEED_MAX: f32 = 26.;
+
+const SERVE_TOSS_HEIGHT: f32 = 3.;
+const SERVE_TOSS_SPEED: f32 = 11.3;
+const SERVE_CONTACT_SECS: f32 = 1.3;
+const SERVE_SPEED: f32 = 24.;
+/// Shorter than a swing's cooldown, so the server can get to a quick return.
+const SERVE_COOLDOWN_SECS: f32 = 0.4;
+const CPU_SERVE_DELAY_SECS: f32 = 1.;
+
+const POINT_OVER_SECS: f32 = 1.5;
+
+/// The length of a gameplay tick and of every physics step. Headless mode paces frames at it.
+const PHYSICS_STEP_SECS: f32 = 1. / 60.;
+/// Beyond this many ticks a frame, slow frames slow the game down instead of falling behind.
+const MAX_TICKS_PER_FRAME: u32 = 4;
+/// Batch simulation runs this many ticks a frame, since the work done once a frame costs far
+/// more than a tick.
+const SIMULATION_TICKS_PER_FRAME: u32 = 60;
 
 const BG_WIDTH: f32 = 272.;
 const BG_HEIGHT: f32 = 256.;
@@ -46,10 +86,72 @@ fn default<T: Default>() -> T {
 
 // ====== State ======
 
+/// `Paused`, `PointOver` and `GameOver` are pushed on top of `InGame`, which stops its systems
+/// without tearing down the match.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 enum AppState {
     Loading,
+    MainMenu,
     InGame,
+    Paused,
+    /// The point's call is shown for a moment before the next serve.
+    PointOver,
+    GameOver,
+    /// Passed through between matches, so leaving `InGame` tears the old one down and entering it
+    /// again sets the new one up. Replaces whatever states are stacked.
+    NewMatch,
+}
+
+/// Runs gameplay and then a physics step, once per tick of `GameTime`, after `Update`.
+///
+/// Bevy runs systems it isn't given an order for in a different order on every run, so each
+/// gameplay system here is ordered, for a seed and a recording to always play out the same:
+/// the last physics step's contacts are called, then players decide, serve, move and swing,
+/// and the ball takes their hits.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, StageLabel)]
+struct FixedUpdate;
+
+/// Systems that turn the ball's physics contacts into game events, before the rules act on them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+struct ContactSystems;
+
+/// Systems that call where the ball went from its contacts, sending the faults and lets the
+/// serve's rules act on.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+struct CallSystems;
+
+/// Systems that end points by sending `PointOverEvent`. Systems reading it in the same tick run
+/// after them, since the tick after may already be in `PointOver`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+struct PointSystems;
+
+/// Systems that fill in `PlayerIntent` run before the systems that carry intents out.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+struct IntentSystems;
+
+/// Systems that carry out the serve, before players move and swing.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+struct ServeSystems;
+
+/// Systems that carry out `PlayerIntent`s, after which its one-off requests are cleared.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+struct ApplyIntentSystems;
+
+/// Systems that put the ball in play and send it where players hit it, before the rest of the
+/// tick catches up with what players did.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SystemLabel)]
+struct HitSystems;
+
+/// Runs a set while `state` is current, for stages other than `Update`. Bevy's own
+/// `SystemSet::on_update` only works in the stage driving the state, and loops forever elsewhere.
+fn in_state(state: AppState) -> SystemSet {
+    SystemSet::new().with_run_criteria(move |current: Res<State<AppState>>| {
+        if *current.current() == state {
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        }
+    })
 }
 
 // ====== Resources ======
@@ -57,17 +159,276 @@ enum AppState {
 #[derive(Default)]
 struct ResourceHandles(Vec<HandleUntyped>);
 
+/// The gameplay clock. Gameplay systems run in the `FixedUpdate` stage once per tick, and
+/// physics steps once at the end of each, so timings don't depend on the frame rate and a tick
+/// always plays out the same way. A frame runs every tick that has come due, up to
+/// `MAX_TICKS_PER_FRAME`; batch simulation and seeking through a replay run a fixed number every
+/// frame, as fast as frames go.
+struct GameTime {
+    /// Ticks run every frame regardless of real time, if set.
+    fixed_ticks_per_frame: Option<u32>,
+    /// Stops ticks, and with them gameplay and physics.
+    paused: bool,
+    /// Runs a single tick while paused.
+    step: bool,
+    /// How fast real time turns into ticks.
+    speed: f32,
+    /// Ticks run since startup.
+    tick: u64,
+    /// Ticks due and not yet run.
+    pending_ticks: u32,
+    /// Ticks run so far this frame.
+    frame_ticks: u32,
+    /// Real time not yet covered by a tick.
+    overstep: Duration,
+}
+
+impl Default for GameTime {
+    fn default() -> Self {
+        Self {
+            fixed_ticks_per_frame: None,
+            paused: false,
+            step: false,
+            speed: 1.,
+            tick: 0,
+            pending_ticks: 0,
+            frame_ticks: 0,
+            overstep: Duration::ZERO,
+        }
+    }
+}
+
+impl GameTime {
+    fn fixed_ticks_per_frame(ticks: u32) -> Self {
+        Self {
+            fixed_ticks_per_frame: Some(ticks),
+            ..default()
+        }
+    }
+
+    fn delta(&self) -> Duration {
+        Duration::from_secs_f32(PHYSICS_STEP_SECS)
+    }
+
+    fn delta_seconds(&self) -> f32 {
+        PHYSICS_STEP_SECS
+    }
+
+    /// How far to draw things between where they were before the last tick and where they
+    /// are now, so movement stays smooth when frames and ticks don't line up.
+    fn interpolation(&self) -> f32 {
+        if self.fixed_ticks_per_frame.is_some() {
+            return 1.;
+        }
+        (self.overstep.as_secs_f32() / PHYSICS_STEP_SECS).min(1.)
+    }
+}
+
+/// The one source of randomness in the game, so that a seed reproduces a match.
+struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// A setting picked by name from a fixed list, on the command line, in the main menu and in
+/// recordings.
+trait Preset: Clone + PartialEq {
+    fn presets() -> Vec<Self>;
+
+    fn name(&self) -> &'static str;
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::presets()
+            .into_iter()
+            .find(|preset| preset.name() == name)
+    }
+
+    /// Every preset's name, for listing the choices.
+    fn names() -> String {
+        Self::presets()
+            .iter()
+            .map(Self::name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Something a player can do, independent of which keys are bound to it. Left and right are
+/// always screen directions, so the far-side player in a hotseat match isn't mirrored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Swing,
+    AimLeft,
+    AimRight,
+    Pause,
+}
+
+/// The keys bound to each action for one keyboard player. An action may have several keys.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct ActionBindings(Vec<(Action, Vec<KeyCode>)>);
+
+/// One binding set per keyboard player, loaded from and saved to the bindings config file.
+/// Defaults and the file format live in `input`.
+#[derive(Clone, Debug, PartialEq)]
+struct InputBindings {
+    players: Vec<ActionBindings>,
+}
+
+/// Timers the CPU players share, reset with each new match.
 #[derive(Default)]
-struct UserScore(u32);
+struct CpuTimers {
+    /// Since the last hit, to give the CPU a reaction time.
+    secs_since_hit: f32,
+    /// Spent waiting to serve.
+    serve_wait_secs: f32,
+}
 
+/// Connected gamepads by player, indexed like the keyboard binding sets. A disconnected pad
+/// leaves its slot empty for the next one to connect.
 #[derive(Default)]
-struct OpponentScore(u32);
+struct GamepadSlots(Vec<Option<Gamepad>>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Controller {
+    /// Keyboard player using the given binding set, and the gamepad in the same slot.
+    Keyboard(usize),
+    Cpu,
+    /// Plays back a recorded keyboard player's intents.
+    Replay,
+}
+
+/// Who plays the match. Each team lists a controller per slot; only the first slot is used
+/// in singles.
+#[derive(Clone, Debug, PartialEq)]
+struct MatchSetup {
+    doubles: bool,
+    near: [Controller; 2],
+    far: [Controller; 2],
+}
+
+impl Default for MatchSetup {
+    fn default() -> Self {
+        Self {
+            doubles: false,
+            near: [Controller::Keyboard(0), Controller::Cpu],
+            far: [Controller::Cpu, Controller::Cpu],
+        }
+    }
+}
+
+impl MatchSetup {
+    fn team_size(&self) -> u8 {
+        if self.doubles {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn controller(&self, player: Player, slot: u8) -> Controller {
+        match player {
+            Player::User => self.near[slot as usize],
+            Player::Opponent => self.far[slot as usize],
+        }
+    }
+
+    /// Whether both sides have a keyboard player, i.e. a local hotseat match, or a replay of
+    /// one.
+    fn versus(&self) -> bool {
+        let has_keyboard = |team: &[Controller; 2]| {
+            team.iter()
+                .any(|c| matches!(c, Controller::Keyboard(_) | Controller::Replay))
+        };
+        has_keyboard(&self.near) && has_keyboard(&self.far)
+    }
+
+    /// Whether every player is a CPU, so there's nobody to call "you".
+    fn spectating(&self) -> bool {
+        self.near
+            .iter()
+            .chain(&self.far)
+            .all(|c| matches!(c, Controller::Cpu))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ServeAttempt {
+    First,
+    Second,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RallyPhase {
+    /// Players are held in position until the server tosses the ball.
+    AwaitingServe,
+    /// The ball is in the air above the server.
+    Toss,
+    /// The serve has been struck and hasn't landed yet.
+    Serve,
+    /// The ball is in play.
+    Rally,
+    /// The point or serve is over and the ball is waiting to be cleared.
+    Dead,
+}
+
+struct RallyState {
+    phase: RallyPhase,
+    attempt: ServeAttempt,
+    /// Hits so far this point, including the serve.
+    shots: u32,
+    /// Where the last shot first bounced, if it has.
+    landing: Option<Vec3>,
+}
+
+impl Default for RallyState {
+    fn default() -> Self {
+        Self {
+            phase: RallyPhase::AwaitingServe,
+            attempt: ServeAttempt::First,
+            shots: 0,
+            landing: None,
+        }
+    }
+}
 
 // ====== Events ======
 
 struct SpawnPlayerEvent {
     position: WorldPosition,
-    opponent: bool,
+    player: Player,
+    slot: u8,
+    controller: Controller,
 }
 
 struct SpawnBallEvent {
@@ -77,13 +438,80 @@ struct SpawnBallEvent {
 
 struct SpawnCourtEvent;
 
+/// A player released a charge. Sent the same way for keyboard and CPU players.
+struct SwingEvent {
+    player_id: Entity,
+    /// From -1 (screen left) to 1 (screen right).
+    aim: f32,
+    /// Overrides where the shot lands, as with `PlayerIntent::target`.
+    target: Option<Vec2>,
+}
+
+/// A player hit the ball.
+#[derive(Clone, Debug)]
 struct HitEvent {
     ball_id: Entity,
+    player: Player,
     new_velocity: Vec3,
 }
 
+/// The ball bounced on the floor.
+struct BounceEvent {
+    position: Vec3,
+}
+
+/// The ball touched the net.
+struct NetTouchEvent;
+
+/// The ball hit the wall.
+struct WallHitEvent;
+
+/// The ball touched a player's body, as opposed to being hit by their swing.
+struct PlayerTouchEvent {
+    player: Player,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum PointReason {
+    /// The ball landed past the baseline.
+    OutLong,
+    /// The ball landed past a sideline.
+    OutWide,
+    /// The ball didn't make it over the net.
+    Net,
+    DoubleFault,
+    /// The ball bounced in and wasn't returned before it bounced again or reached the wall.
+    Winner,
+    /// The serve was never touched by the receiver.
+    Ace,
+    /// The ball was hit into the hitter's own side of the court.
+    UnforcedError,
+    /// The ball touched a player, who loses the point.
+    Touched,
+}
+
+impl PointReason {
+    fn call(self) -> &'static str {
+        match self {
+            PointReason::OutLong => "Out!",
+            PointReason::OutWide => "Wide!",
+            PointReason::Net => "Net!",
+            PointReason::DoubleFault => "Double fault!",
+            PointReason::Winner => "Winner!",
+            PointReason::Ace => "Ace!",
+            PointReason::UnforcedError => "Error!",
+            PointReason::Touched => "Touched!",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 struct PointOverEvent {
     winner: Player,
+    reason: PointReason,
+    /// Where the last shot bounced, if it did.
+    landing: Option<Vec3>,
+    last_hitter: Player,
 }
 
 struct GameOverEvent;
@@ -91,11 +519,25 @@ struct GameOverEvent;
 #[derive(Default)]
 struct BallBouncesSinceHit(u32);
 
+#[derive(Default)]
+struct BallTouchedNet(bool);
+
+struct FaultEvent {
+    /// Where the serve bounced, if it did.
+    landing: Option<Vec3>,
+}
+
+struct LetEvent;
+
 // ====== Components ======
 
 #[derive(Component, Clone, Copy, Debug, Default)]
 struct WorldPosition(Vec3);
 
+/// Where an entity was before the last tick, to draw it in between.
+#[derive(Component, Clone, Copy, Debug, Default)]
+struct PreviousWorldPosition(Vec3);
+
 #[derive(Component, Default)]
 struct WorldSprite {
     base: Vec2,
@@ -104,6 +546,10 @@ struct WorldSprite {
 #[derive(Component)]
 struct SyncWorldPosition;
 
+/// Part of the scene set up on entering the game, and despawned on leaving it.
+#[derive(Component)]
+struct SceneEntity;
+
 #[derive(Component)]
 struct MainCamera;
 
@@ -127,32 +573,80 @@ struct SpriteAnimationFrame {
     duration: Duration,
 }
 
-#[derive(Component)]
-struct CustomScale(f32);
+/// What a court collider is, for telling apart the ball's contacts.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+enum Surface {
+    Floor,
+    Net,
+    Wall,
+}
 
 // ====== Player components ======
 
-#[derive(Component, Debug)]
+/// The side of the court a player is on: the user's team is always at the near end.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
 enum Player {
     User,
     Opponent,
 }
 
+impl Player {
+    fn other(self) -> Self {
+        match self {
+            Player::User => Player::Opponent,
+            Player::Opponent => Player::User,
+        }
+    }
+}
+
 #[derive(Component, Clone)]
 enum PlayerState {
     Idle,
     Run,
     Charge,
     Swing,
+    Serve,
 }
 
+/// Which member of the team this is; always 0 in singles.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+struct PlayerSlot(u8);
+
+/// The range of x coordinates a CPU player covers, so doubles partners split the court.
 #[derive(Component)]
-struct PlayerSpeed(f32);
+struct CoverZone {
+    x_min: f32,
+    x_max: f32,
+}
 
+/// Current speed, which drops while charging and swinging.
 #[derive(Component)]
-struct PlayerDirection(Vec3);
+struct PlayerSpeed(f32);
 
+/// Speed when free to run, set by the controller and court surface.
 #[derive(Component)]
+struct TopSpeed(f32);
+
+/// What a player wants to do this tick, whatever is controlling it: the keyboard or a gamepad,
+/// the CPU, or anything else that can fill it in. Shared systems turn intents into movement,
+/// charges, swings and serve tosses, so every kind of controller plays by the same rules.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+struct PlayerIntent {
+    /// At most unit length.
+    movement: Vec2,
+    /// From -1 (screen left) to 1 (screen right), used when releasing a swing or serving.
+    aim: f32,
+    /// Start charging a swing, or toss the ball when it's this player's serve. Cleared once
+    /// a tick has acted on it.
+    start_charge: bool,
+    /// Release the charge and swing. Cleared once a tick has acted on it.
+    release: bool,
+    /// Where the released swing lands instead of where aim and charge send it, for a CPU
+    /// missing on purpose. Cleared with `release`.
+    target: Option<Vec2>,
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
 enum PlayerFacing {
     Right,
     Left,
@@ -161,15 +655,64 @@ enum PlayerFacing {
 #[derive(Component)]
 struct SwingCooldown(Timer);
 
+/// How long the current charge has been held, zero when not charging.
+#[derive(Component, Default)]
+struct ChargeTime(f32);
+
 #[derive(Component)]
-struct UserControlled;
+struct ServeToss(Timer);
 
+/// Keyboard player using the given binding set.
 #[derive(Component)]
-struct Opponent;
+struct UserControlled(usize);
+
+/// Actions held by a keyboard or gamepad player this frame and the last, updated before any
+/// game system runs.
+#[derive(Component, Default)]
+struct ActionState {
+    pressed: HashSet<Action>,
+    previous: HashSet<Action>,
+}
+
+impl ActionState {
+    fn just_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action) && !self.previous.contains(&action)
+    }
+
+    fn just_released(&self, action: Action) -> bool {
+        !self.pressed.contains(&action) && self.previous.contains(&action)
+    }
+}
 
 #[derive(Component)]
 struct CpuControlled;
 
+/// Player whose intents come from the replay being played back.
+#[derive(Component)]
+struct ReplayControlled;
+
+/// A CPU player's style, whether it's currently heading for the net, and the shot it means
+/// to play next.
+#[derive(Component)]
+struct CpuPlan {
+    style: CpuStyle,
+    at_net: bool,
+    /// Where and how soon the CPU expects to be able to hit the ball.
+    intercept: Option<PathPoint>,
+    /// Which way the CPU will face to hit the coming ball, picked when it first sees where to
+    /// meet it.
+    facing: Option<PlayerFacing>,
+    shot: Option<CpuShot>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CpuShot {
+    aim: f32,
+    charge_secs: f32,
+    /// Where a deliberate miss lands.
+    target: Option<Vec2>,
+}
+
 // ====== Ball components ======
 
 #[derive(Component)]
@@ -178,6 +721,9 @@ struct GameBall;
 #[derive(Component)]
 struct GameBallShadow;
 
+/// Filters for the ball and its shadow, to take the ball out of play.
+type BallOrShadow = Or<(With<GameBall>, With<GameBallShadow>)>;
+
 #[derive(Component)]
 struct LastHitBy(Player);
 
@@ -190,18 +736,93 @@ struct OpponentScoreText;
 #[derive(Component)]
 struct ResultsText;
 
+/// The game as launched with `options`: its settings, setup, input and gameplay, but none of
+/// the menus, HUD or recording around them. Used by `main` and by headless tests alike.
+fn game_app(options: &options::LaunchOptions, after_loading: AppState) -> App {
+    let simulating = options.simulate.is_some();
+    let mut app = App::new();
+    app.insert_resource(WindowDescriptor {
+        width: BG_WIDTH * PX_SCALE,
+        height: BG_HEIGHT * PX_SCALE,
+        resizable: false,
+        vsync: !simulating,
+        ..Default::default()
+    })
+    .insert_resource(options.match_format.clone())
+    .insert_resource(options.match_setup.clone())
+    .insert_resource(options.court_surface.clone())
+    .insert_resource(options.cpu_difficulty.clone())
+    .insert_resource(options.cpu_style)
+    .insert_resource(GameRng::new(options.seed));
+    if simulating {
+        app.insert_resource(GameTime::fixed_ticks_per_frame(SIMULATION_TICKS_PER_FRAME));
+    } else if options.headless {
+        // nothing paces frames without a window, so wait out a tick between them
+        app.insert_resource(bevy::app::ScheduleRunnerSettings::run_loop(
+            Duration::from_secs_f32(PHYSICS_STEP_SECS),
+        ));
+    }
+    app.add_plugin(setup::SetupPlugin {
+        headless: options.headless,
+        after_loading,
+    })
+    .add_plugin(input::InputPlugin {
+        save_defaults: !options.headless && !simulating,
+    })
+    .add_plugin(game::GamePlugin);
+    app
+}
+
 fn main() {
-    App::new()
-        .insert_resource(WindowDescriptor {
-            width: BG_WIDTH * PX_SCALE,
-            height: BG_HEIGHT * PX_SCALE,
-            resizable: false,
-            vsync: true,
-            ..Default::default()
-        })
-        .add_plugin(setup::SetupPlugin)
-        .add_plugin(ui::UiPlugin)
-        .add_plugin(game::GamePlugin)
-        .add_state(AppState::Loading)
-        .run();
+    let mut options = options::LaunchOptions::from_args();
+    let replay = options
+        .replay
+        .as_deref()
+        .and_then(|path| match replay::Recording::load(path) {
+            Ok(recording) => Some(recording),
+            Err(err) => {
+                eprintln!("couldn't load replay {path}: {err}");
+                None
+            }
+        });
+    if let Some(replay) = &replay {
+        replay.apply(&mut options);
+        if options.record.is_some() {
+            eprintln!("ignoring --record while playing a replay");
+        }
+    }
+    let record = options
+        .record
+        .clone()
+        .filter(|_| replay.is_none())
+        .map(|path| replay::RecordPlugin::new(path, &options));
+    // recordings keep the settings launched with, so recording skips the main menu, though not
+    // the pause and game over screens, and replays have no menus at all
+    let interactive = !options.headless && options.simulate.is_none() && replay.is_none();
+    let after_loading = if interactive && record.is_none() {
+        AppState::MainMenu
+    } else {
+        AppState::InGame
+    };
+    let mut app = game_app(&options, after_loading);
+    if let Some(matches) = options.simulate {
+        app.add_plugin(simulation::SimulationPlugin { matches });
+    }
+    if options.headless {
+        app.add_plugin(bevy::log::LogPlugin);
+    } else {
+        app.add_plugin(ui::UiPlugin);
+    }
+    if interactive {
+        app.add_plugin(menu::MenuPlugin {
+            main_menu: record.is_none(),
+        });
+    }
+    if let Some(recording) = replay {
+        app.add_plugin(replay::PlaybackPlugin { recording });
+    }
+    if let Some(record) = record {
+        app.add_plugin(record);
+    }
+    app.add_state(AppState::Loading).run();
 }
@@ -0,0 +1,308 @@
+//! The main menu, pause and game over screens.
+//!
+//! Each screen spawns its text on entering its state and despawns it on leaving. The main menu
+//! edits the same settings resources the command line sets, which the match reads as it starts.
+
+use crate::*;
+use bevy::app::AppExit;
+
+const MENU_ROWS: [&str; 5] = ["Format", "Players", "Court", "CPU", "Style"];
+
+pub(crate) struct MenuPlugin {
+    /// Whether there's a main menu to start from and go back to. Without one, as when
+    /// recording, the pause and game over screens offer quitting instead.
+    pub(crate) main_menu: bool,
+}
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        if self.main_menu {
+            app.init_resource::<MenuRow>()
+                .add_system_set(
+                    SystemSet::on_enter(AppState::MainMenu).with_system(spawn_main_menu),
+                )
+                .add_system_set(
+                    SystemSet::on_update(AppState::MainMenu)
+                        .with_system(main_menu_controls_system)
+                        .with_system(change_setting_system)
+                        .with_system(main_menu_text_system),
+                )
+                .add_system_set(
+                    SystemSet::on_exit(AppState::MainMenu).with_system(despawn_menu_text_system),
+                );
+        }
+        app.insert_resource(HasMainMenu(self.main_menu))
+            // in every state, since the state toggled into runs its systems in the same frame and
+            // would see the same press
+            .add_system(pause_system)
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(enter_pause_system))
+            .add_system_set(SystemSet::on_update(AppState::Paused).with_system(paused_menu_system))
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused)
+                    .with_system(exit_pause_system)
+                    .with_system(despawn_menu_text_system),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(spawn_game_over_menu),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::GameOver).with_system(game_over_menu_system),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::GameOver).with_system(despawn_menu_text_system),
+            );
+    }
+}
+
+/// Text belonging to whichever menu screen is showing.
+#[derive(Component)]
+struct MenuText;
+
+/// Whether the pause and game over screens can go back to the main menu.
+struct HasMainMenu(bool);
+
+/// The main menu row being changed, indexing `MENU_ROWS`.
+#[derive(Default)]
+struct MenuRow(usize);
+
+fn spawn_menu_text(commands: &mut Commands, asset_server: &AssetServer, top: f32, value: &str) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(top),
+                    left: Val::Px(60.0),
+                    ..default()
+                },
+                ..default()
+            },
+            text: Text::with_section(
+                value,
+                TextStyle {
+                    font: asset_server.load("fonts/Press_Start_2P/PressStart2P-Regular.ttf"),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+                default(),
+            ),
+            ..default()
+        })
+        .insert(MenuText);
+}
+
+fn despawn_menu_text_system(mut commands: Commands, query: Query<Entity, With<MenuText>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// ====== Main menu ======
+
+fn spawn_main_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut row: ResMut<MenuRow>,
+) {
+    row.0 = 0;
+    // filled in by `main_menu_text_system`
+    spawn_menu_text(&mut commands, &asset_server, 120.0, "");
+}
+
+fn main_menu_controls_system(
+    keys: Res<Input<KeyCode>>,
+    mut row: ResMut<MenuRow>,
+    mut state: ResMut<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if keys.just_pressed(KeyCode::Up) {
+        row.0 = (row.0 + MENU_ROWS.len() - 1) % MENU_ROWS.len();
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        row.0 = (row.0 + 1) % MENU_ROWS.len();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        state.set(AppState::InGame).unwrap();
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        exit.send(AppExit);
+    }
+}
+
+fn change_setting_system(
+    keys: Res<Input<KeyCode>>,
+    row: Res<MenuRow>,
+    mut format: ResMut<MatchFormat>,
+    mut setup: ResMut<MatchSetup>,
+    mut surface: ResMut<CourtSurface>,
+    mut difficulty: ResMut<CpuDifficulty>,
+    mut style: ResMut<CpuStyle>,
+) {
+    let forward = keys.just_pressed(KeyCode::Right);
+    if !forward && !keys.just_pressed(KeyCode::Left) {
+        return;
+    }
+    match MENU_ROWS[row.0] {
+        "Format" => *format = cycle(&format, forward),
+        "Players" => setup.doubles = !setup.doubles,
+        "Court" => *surface = cycle(&surface, forward),
+        "CPU" => *difficulty = cycle(&difficulty, forward),
+        "Style" => *style = cycle(&style, forward),
+        _ => unreachable!(),
+    }
+}
+
+fn main_menu_text_system(
+    row: Res<MenuRow>,
+    format: Res<MatchFormat>,
+    setup: Res<MatchSetup>,
+    surface: Res<CourtSurface>,
+    difficulty: Res<CpuDifficulty>,
+    style: Res<CpuStyle>,
+    mut query: Query<&mut Text, With<MenuText>>,
+) {
+    let values = [
+        format.name(),
+        if setup.doubles { "doubles" } else { "singles" },
+        surface.name(),
+        difficulty.name(),
+        style.name(),
+    ];
+    let mut value = String::from("TENNIS\n\n");
+    for (i, (name, setting)) in MENU_ROWS.iter().zip(values).enumerate() {
+        let cursor = if i == row.0 { '>' } else { ' ' };
+        value += &format!("{cursor} {name:<8}< {setting} >\n");
+    }
+    value += "\nUp/Down: choose\nLeft/Right: change\nEnter: play\nEsc: quit";
+    for mut text in query.iter_mut() {
+        // only touched on a change, so the text isn't laid out again every frame
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// The preset after `current`, or before it going back, wrapping around. A value that isn't one
+/// of the presets, like one loaded from a replay, moves to the first.
+fn cycle<T: Preset>(current: &T, forward: bool) -> T {
+    let presets = T::presets();
+    let len = presets.len();
+    let next = match presets.iter().position(|preset| preset == current) {
+        Some(i) if forward => (i + 1) % len,
+        Some(i) => (i + len - 1) % len,
+        None => 0,
+    };
+    presets[next].clone()
+}
+
+// ====== Pause ======
+
+/// Pauses on any user's pause action, and resumes on the next. Play can be paused during a
+/// point or the pause after it; the game over screen has its own choices, so a press there
+/// does nothing. A press in a frame that already has a state change queued, like the end of a
+/// point, is held until the next frame.
+fn pause_system(
+    mut state: ResMut<State<AppState>>,
+    mut pending: Local<bool>,
+    query: Query<&ActionState, With<UserControlled>>,
+) {
+    if query
+        .iter()
+        .any(|actions| actions.just_pressed(Action::Pause))
+    {
+        *pending = !*pending;
+    }
+    if !*pending {
+        return;
+    }
+    let toggled = match state.current() {
+        AppState::InGame | AppState::PointOver => state.push(AppState::Paused),
+        AppState::Paused => state.pop(),
+        _ => Ok(()),
+    };
+    // still pending if another transition got there first
+    *pending = toggled.is_err();
+}
+
+fn enter_pause_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    has_main_menu: Res<HasMainMenu>,
+    mut time: ResMut<GameTime>,
+) {
+    // no ticks, so timers hold and recorded intents stay in step
+    time.paused = true;
+    let text = if has_main_menu.0 {
+        "PAUSED\n\nEsc: resume\nM: main menu"
+    } else {
+        "PAUSED\n\nEsc: resume\nQ: quit"
+    };
+    spawn_menu_text(&mut commands, &asset_server, 200.0, text);
+}
+
+fn paused_menu_system(
+    keys: Res<Input<KeyCode>>,
+    has_main_menu: Res<HasMainMenu>,
+    mut state: ResMut<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if has_main_menu.0 && keys.just_pressed(KeyCode::M) {
+        state.replace(AppState::MainMenu).unwrap();
+    } else if !has_main_menu.0 && keys.just_pressed(KeyCode::Q) {
+        exit.send(AppExit);
+    }
+}
+
+fn exit_pause_system(mut time: ResMut<GameTime>) {
+    time.paused = false;
+}
+
+// ====== Game over ======
+
+fn spawn_game_over_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    has_main_menu: Res<HasMainMenu>,
+) {
+    let text = if has_main_menu.0 {
+        "GAME OVER\n\nEnter: rematch\nM: main menu"
+    } else {
+        "GAME OVER\n\nEnter: rematch\nQ: quit"
+    };
+    spawn_menu_text(&mut commands, &asset_server, 200.0, text);
+}
+
+fn game_over_menu_system(
+    keys: Res<Input<KeyCode>>,
+    has_main_menu: Res<HasMainMenu>,
+    mut state: ResMut<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if keys.just_pressed(KeyCode::Return) {
+        state.replace(AppState::NewMatch).unwrap();
+    } else if has_main_menu.0 && keys.just_pressed(KeyCode::M) {
+        state.replace(AppState::MainMenu).unwrap();
+    } else if !has_main_menu.0 && keys.just_pressed(KeyCode::Q) {
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_wraps_around_presets() {
+        assert_eq!(cycle(&CpuStyle::Baseliner, true), CpuStyle::ServeAndVolley);
+        assert_eq!(cycle(&CpuStyle::Pusher, true), CpuStyle::Baseliner);
+        assert_eq!(cycle(&CpuStyle::Baseliner, false), CpuStyle::Pusher);
+    }
+
+    #[test]
+    fn cycle_from_a_custom_value_starts_at_the_first_preset() {
+        let mut custom = MatchFormat::standard();
+        custom.name = "custom";
+        custom.target_score += 1;
+        assert_eq!(cycle(&custom, false), MatchFormat::standard());
+    }
+}
@@ -0,0 +1,125 @@
+//! Command line options.
+//!
+//! Parsed once in `main` before the app is built. Unknown or malformed arguments are reported
+//! and otherwise ignored, since the web build has no command line at all.
+
+use crate::*;
+
+pub(crate) struct LaunchOptions {
+    pub(crate) match_format: MatchFormat,
+    pub(crate) match_setup: MatchSetup,
+    pub(crate) court_surface: CourtSurface,
+    pub(crate) cpu_difficulty: CpuDifficulty,
+    pub(crate) cpu_style: CpuStyle,
+    /// Number of CPU-only matches to play back to back before reporting and exiting.
+    pub(crate) simulate: Option<u32>,
+    pub(crate) headless: bool,
+    /// Seeds every random decision. Picked at random unless given.
+    pub(crate) seed: u64,
+    /// Where to save a recording of the match.
+    pub(crate) record: Option<String>,
+    /// A recording to play back instead of playing.
+    pub(crate) replay: Option<String>,
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self {
+            match_format: default(),
+            match_setup: default(),
+            court_surface: default(),
+            cpu_difficulty: default(),
+            cpu_style: default(),
+            simulate: None,
+            headless: false,
+            seed: rand::random(),
+            record: None,
+            replay: None,
+        }
+    }
+}
+
+impl LaunchOptions {
+    pub(crate) fn from_args() -> Self {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => parse_preset(&arg, args.next(), &mut options.match_format),
+                "--surface" => parse_preset(&arg, args.next(), &mut options.court_surface),
+                "--difficulty" => parse_preset(&arg, args.next(), &mut options.cpu_difficulty),
+                "--style" => parse_preset(&arg, args.next(), &mut options.cpu_style),
+                "--doubles" => options.match_setup.doubles = true,
+                "--headless" => options.headless = true,
+                "--record" | "--replay" => match args.next() {
+                    Some(path) if arg == "--record" => options.record = Some(path),
+                    Some(path) => options.replay = Some(path),
+                    None => eprintln!("{arg} expects a file path"),
+                },
+                "--seed" => match args.next().and_then(|seed| seed.parse().ok()) {
+                    Some(seed) => options.seed = seed,
+                    None => eprintln!("--seed expects a number"),
+                },
+                "--spectate" => options.match_setup.near = [Controller::Cpu; 2],
+                "--simulate" => match args.next().and_then(|n| n.parse().ok()) {
+                    Some(matches) if matches > 0 => {
+                        options.match_setup.near = [Controller::Cpu; 2];
+                        options.match_setup.far = [Controller::Cpu; 2];
+                        options.simulate = Some(matches);
+                    }
+                    _ => eprintln!("--simulate expects a number of matches"),
+                },
+                "--versus" => {
+                    options.match_setup.far[0] = Controller::Keyboard(1);
+                }
+                "--near" | "--far" => {
+                    let team = if arg == "--near" {
+                        &mut options.match_setup.near
+                    } else {
+                        &mut options.match_setup.far
+                    };
+                    let value = args.next().unwrap_or_default();
+                    for (slot, name) in value.split(',').enumerate().take(2) {
+                        match Controller::from_name(name) {
+                            Some(controller) => team[slot] = controller,
+                            None => eprintln!("{arg} expects a list of: user, user2, cpu"),
+                        }
+                    }
+                }
+                _ => eprintln!("ignoring unknown argument {arg:?}"),
+            }
+        }
+        options
+    }
+}
+
+/// Sets `setting` to the preset `flag` names, or reports the choices.
+fn parse_preset<T: Preset>(flag: &str, name: Option<String>, setting: &mut T) {
+    match name.as_deref().and_then(T::from_name) {
+        Some(preset) => *setting = preset,
+        None => eprintln!("{flag} expects one of: {}", T::names()),
+    }
+}
+
+impl Controller {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "user" => Some(Self::Keyboard(0)),
+            "user2" => Some(Self::Keyboard(1)),
+            "cpu" => Some(Self::Cpu),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Keyboard(0) => "user",
+            Self::Keyboard(_) => "user2",
+            Self::Cpu => "cpu",
+            Self::Replay => "replay",
+        }
+    }
+}
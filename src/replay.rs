@@ -0,0 +1,571 @@
+//! Records matches and plays them back.
+//!
+//! A recording is the seed and settings a match started with, plus the intents of its keyboard
+//! players on every tick they changed. Everything else, CPU players included, follows from
+//! those, since a tick always plays out the same way. Playback puts `ReplayControlled` players
+//! in the keyboard players' places and feeds them the recorded intents.
+//!
+//! The file is plain text: `setting = value` lines, then a `[points]` section with the tick
+//! each point ended on, then an `[intents]` section with a `tick side slot x y aim flags` line
+//! per change, where the flags are `c` for starting a charge and `r` for releasing it.
+//!
+//! During playback P pauses, `.` steps a single tick while paused, up and down change the
+//! speed, left and right skip to the previous and next point, and Home starts over.
+
+use crate::options::LaunchOptions;
+use crate::*;
+
+/// Skipping back within this many ticks of a point's start goes to the point before instead,
+/// like a media player's previous button.
+const REWIND_GRACE_TICKS: u64 = 60;
+const MIN_PLAYBACK_SPEED: f32 = 0.25;
+const MAX_PLAYBACK_SPEED: f32 = 4.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct RecordedIntent {
+    /// Counted from the first tick of the match.
+    tick: u64,
+    player: Player,
+    slot: u8,
+    intent: PlayerIntent,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Recording {
+    seed: u64,
+    format: MatchFormat,
+    surface: CourtSurface,
+    difficulty: CpuDifficulty,
+    style: CpuStyle,
+    setup: MatchSetup,
+    /// Ticks on which points ended, to skip between.
+    points: Vec<u64>,
+    intents: Vec<RecordedIntent>,
+}
+
+impl Recording {
+    fn new(options: &LaunchOptions) -> Self {
+        Self {
+            seed: options.seed,
+            format: options.match_format.clone(),
+            surface: options.court_surface.clone(),
+            difficulty: options.cpu_difficulty.clone(),
+            style: options.cpu_style,
+            setup: options.match_setup.clone(),
+            points: Vec::new(),
+            intents: Vec::new(),
+        }
+    }
+
+    /// Starts the match the way the recorded one started, with replayed players in place of
+    /// keyboard ones.
+    pub(crate) fn apply(&self, options: &mut LaunchOptions) {
+        options.seed = self.seed;
+        options.match_format = self.format.clone();
+        options.court_surface = self.surface.clone();
+        options.cpu_difficulty = self.difficulty.clone();
+        options.cpu_style = self.style;
+        options.match_setup = self.setup.clone();
+        for controller in options
+            .match_setup
+            .near
+            .iter_mut()
+            .chain(&mut options.match_setup.far)
+        {
+            if let Controller::Keyboard(_) = controller {
+                *controller = Controller::Replay;
+            }
+        }
+    }
+
+    pub(crate) fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&text)
+    }
+
+    fn save(&self, path: &str) {
+        if let Err(err) = std::fs::write(path, self.to_text()) {
+            warn!("couldn't write {path}: {err}");
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut recording = Self::new(&LaunchOptions::default());
+        let mut seed = None;
+        let mut section = None;
+        for (number, line) in text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+        {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                match name {
+                    "points" | "intents" => section = Some(name),
+                    _ => return Err(format!("line {number}: unknown section [{name}]")),
+                }
+                continue;
+            }
+            match section {
+                None => {
+                    let (key, value) = line
+                        .split_once('=')
+                        .ok_or_else(|| format!("line {number}: expected `setting = value`"))?;
+                    let (key, value) = (key.trim(), value.trim());
+                    let invalid = || format!("line {number}: invalid {key} {value:?}");
+                    match key {
+                        "seed" => seed = Some(value.parse().map_err(|_| invalid())?),
+                        "format" => {
+                            recording.format = MatchFormat::from_name(value).ok_or_else(invalid)?
+                        }
+                        "surface" => {
+                            recording.surface =
+                                CourtSurface::from_name(value).ok_or_else(invalid)?
+                        }
+                        "difficulty" => {
+                            recording.difficulty =
+                                CpuDifficulty::from_name(value).ok_or_else(invalid)?
+                        }
+                        "style" => {
+                            recording.style = CpuStyle::from_name(value).ok_or_else(invalid)?
+                        }
+                        "doubles" => {
+                            recording.setup.doubles = value.parse().map_err(|_| invalid())?
+                        }
+                        "near" | "far" => {
+                            let controllers: Option<Vec<_>> = value
+                                .split(',')
+                                .map(|name| Controller::from_name(name.trim()))
+                                .collect();
+                            let team = match controllers.as_deref() {
+                                Some(&[first, second]) => [first, second],
+                                _ => return Err(invalid()),
+                            };
+                            if key == "near" {
+                                recording.setup.near = team;
+                            } else {
+                                recording.setup.far = team;
+                            }
+                        }
+                        _ => return Err(format!("line {number}: unknown setting {key:?}")),
+                    }
+                }
+                Some("points") => {
+                    let tick = line
+                        .parse()
+                        .map_err(|_| format!("line {number}: invalid tick {line:?}"))?;
+                    recording.points.push(tick);
+                }
+                Some(_) => {
+                    let intent = parse_intent(line).ok_or_else(|| {
+                        format!("line {number}: expected `tick side slot x y aim flags`")
+                    })?;
+                    recording.intents.push(intent);
+                }
+            }
+        }
+        recording.seed = seed.ok_or_else(|| "missing seed".to_owned())?;
+        Ok(recording)
+    }
+
+    fn to_text(&self) -> String {
+        let team = |team: &[Controller; 2]| team.map(Controller::name).join(", ");
+        let mut text = format!(
+            "# Replay: the seed and settings of the match, the ticks its points ended on, and\n\
+             # every change in its keyboard players' intents.\n\
+             seed = {}\nformat = {}\nsurface = {}\ndifficulty = {}\nstyle = {}\n\
+             doubles = {}\nnear = {}\nfar = {}\n",
+            self.seed,
+            self.format.name(),
+            self.surface.name(),
+            self.difficulty.name(),
+            self.style.name(),
+            self.setup.doubles,
+            team(&self.setup.near),
+            team(&self.setup.far),
+        );
+        text += "\n[points]\n";
+        for tick in &self.points {
+            text += &format!("{tick}\n");
+        }
+        text += "\n[intents]\n";
+        for record in &self.intents {
+            let intent = &record.intent;
+            let flags = match (intent.start_charge, intent.release) {
+                (false, false) => "-",
+                (true, false) => "c",
+                (false, true) => "r",
+                (true, true) => "cr",
+            };
+            text += &format!(
+                "{} {} {} {} {} {} {flags}\n",
+                record.tick,
+                side_name(record.player),
+                record.slot,
+                intent.movement.x,
+                intent.movement.y,
+                intent.aim,
+            );
+        }
+        text
+    }
+}
+
+fn side_name(player: Player) -> &'static str {
+    match player {
+        Player::User => "near",
+        Player::Opponent => "far",
+    }
+}
+
+fn parse_intent(line: &str) -> Option<RecordedIntent> {
+    let mut fields = line.split_whitespace();
+    let tick = fields.next()?.parse().ok()?;
+    let player = match fields.next()? {
+        "near" => Player::User,
+        "far" => Player::Opponent,
+        _ => return None,
+    };
+    let slot = fields.next()?.parse().ok()?;
+    let x = fields.next()?.parse().ok()?;
+    let y = fields.next()?.parse().ok()?;
+    let aim = fields.next()?.parse().ok()?;
+    let flags = fields.next()?;
+    if fields.next().is_some() || !matches!(flags, "-" | "c" | "r" | "cr") {
+        return None;
+    }
+    Some(RecordedIntent {
+        tick,
+        player,
+        slot,
+        intent: PlayerIntent {
+            movement: Vec2::new(x, y),
+            aim,
+            start_charge: flags.contains('c'),
+            release: flags.contains('r'),
+            target: None,
+        },
+    })
+}
+
+// ====== Recording ======
+
+pub(crate) struct RecordPlugin {
+    path: String,
+    recording: Recording,
+}
+
+impl RecordPlugin {
+    pub(crate) fn new(path: String, options: &LaunchOptions) -> Self {
+        Self {
+            path,
+            recording: Recording::new(options),
+        }
+    }
+}
+
+impl Plugin for RecordPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Recorder {
+            path: self.path.clone(),
+            recording: self.recording.clone(),
+            start_tick: 0,
+            finished: false,
+        })
+        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(start_recording_system))
+        .add_system_set(SystemSet::on_enter(AppState::GameOver).with_system(stop_recording_system))
+        .add_system_set(SystemSet::on_exit(AppState::InGame).with_system(stop_recording_system))
+        .add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .after(IntentSystems)
+                .before(ApplyIntentSystems)
+                .with_system(record_intents_system),
+        )
+        .add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .after(PointSystems)
+                .with_system(record_points_system),
+        );
+    }
+}
+
+struct Recorder {
+    path: String,
+    recording: Recording,
+    start_tick: u64,
+    /// Only the first match is recorded, up to its end or leaving it for the main menu; the ones
+    /// after it don't start from the recorded seed.
+    finished: bool,
+}
+
+fn start_recording_system(time: Res<GameTime>, mut recorder: ResMut<Recorder>) {
+    recorder.start_tick = time.tick;
+}
+
+fn stop_recording_system(mut recorder: ResMut<Recorder>) {
+    recorder.finished = true;
+}
+
+fn record_intents_system(
+    time: Res<GameTime>,
+    mut recorder: ResMut<Recorder>,
+    query: Query<(&Player, &PlayerSlot, &PlayerIntent), With<UserControlled>>,
+) {
+    if recorder.finished {
+        return;
+    }
+    let tick = time.tick - recorder.start_tick;
+    for (player, slot, intent) in query.iter() {
+        let previous = recorder
+            .recording
+            .intents
+            .iter()
+            .rev()
+            .find(|record| record.player == *player && record.slot == slot.0)
+            .map_or_else(PlayerIntent::default, |record| record.intent);
+        if *intent != previous {
+            recorder.recording.intents.push(RecordedIntent {
+                tick,
+                player: *player,
+                slot: slot.0,
+                intent: *intent,
+            });
+        }
+    }
+}
+
+/// Marks where each point ended and saves the recording so far.
+fn record_points_system(
+    time: Res<GameTime>,
+    mut recorder: ResMut<Recorder>,
+    mut point_over_events: EventReader<PointOverEvent>,
+) {
+    if point_over_events.iter().count() == 0 || recorder.finished {
+        return;
+    }
+    let tick = time.tick - recorder.start_tick;
+    recorder.recording.points.push(tick);
+    recorder.recording.save(&recorder.path);
+}
+
+// ====== Playback ======
+
+pub(crate) struct PlaybackPlugin {
+    pub(crate) recording: Recording,
+}
+
+impl Plugin for PlaybackPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Playback {
+            recording: self.recording.clone(),
+            start_tick: 0,
+            cursor: 0,
+            seek_to: None,
+        })
+        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(start_playback_system))
+        // in every state, to skip about between points and after the match
+        .add_system(playback_controls_system)
+        .add_system(seek_system)
+        .add_system(vsync_system)
+        .add_system_set_to_stage(
+            FixedUpdate,
+            in_state(AppState::InGame)
+                .label(IntentSystems)
+                .after(PointSystems)
+                .with_system(replay_intents_system),
+        );
+    }
+}
+
+struct Playback {
+    recording: Recording,
+    start_tick: u64,
+    /// The next recorded intent to play.
+    cursor: usize,
+    /// The tick being skipped to.
+    seek_to: Option<u64>,
+}
+
+fn start_playback_system(time: Res<GameTime>, mut playback: ResMut<Playback>) {
+    playback.start_tick = time.tick;
+}
+
+fn replay_intents_system(
+    time: Res<GameTime>,
+    mut playback: ResMut<Playback>,
+    mut query: Query<(&Player, &PlayerSlot, &mut PlayerIntent), With<ReplayControlled>>,
+) {
+    let tick = time.tick - playback.start_tick;
+    while let Some(record) = playback
+        .recording
+        .intents
+        .get(playback.cursor)
+        .filter(|record| record.tick <= tick)
+        .copied()
+    {
+        for (player, slot, mut intent) in query.iter_mut() {
+            if *player == record.player && slot.0 == record.slot {
+                *intent = record.intent;
+            }
+        }
+        playback.cursor += 1;
+    }
+}
+
+fn playback_controls_system(
+    keys: Res<Input<KeyCode>>,
+    mut time: ResMut<GameTime>,
+    mut playback: ResMut<Playback>,
+) {
+    if keys.just_pressed(KeyCode::P) {
+        time.paused = !time.paused;
+    }
+    if keys.just_pressed(KeyCode::Period) && time.paused {
+        time.step = true;
+    }
+    if keys.just_pressed(KeyCode::Up) || keys.just_pressed(KeyCode::Down) {
+        time.speed = if keys.just_pressed(KeyCode::Up) {
+            time.speed * 2.
+        } else {
+            time.speed / 2.
+        }
+        .clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED);
+        info!("playback speed {}x", time.speed);
+    }
+    let tick = time.tick - playback.start_tick;
+    let points = &playback.recording.points;
+    let seek_to = if keys.just_pressed(KeyCode::Right) {
+        points.iter().copied().find(|&point| point > tick)
+    } else if keys.just_pressed(KeyCode::Left) {
+        Some(
+            points
+                .iter()
+                .copied()
+                .rev()
+                .find(|&point| point + REWIND_GRACE_TICKS < tick)
+                .unwrap_or(0),
+        )
+    } else if keys.just_pressed(KeyCode::Home) {
+        Some(0)
+    } else {
+        None
+    };
+    if seek_to.is_some() {
+        playback.seek_to = seek_to;
+    }
+}
+
+/// Runs ticks as fast as frames go until the tick being skipped to. Going back starts the
+/// match over, since ticks only run forwards: the same seed, and a new match set up before the
+/// next tick like the first one was.
+fn seek_system(
+    mut time: ResMut<GameTime>,
+    mut playback: ResMut<Playback>,
+    mut rng: ResMut<GameRng>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let target = match playback.seek_to {
+        Some(target) => target,
+        None => return,
+    };
+    if target < time.tick - playback.start_tick {
+        // overwriting a point over that's just been queued
+        state.overwrite_replace(AppState::NewMatch).unwrap();
+        *rng = GameRng::new(rng.seed);
+        playback.start_tick = time.tick;
+        playback.cursor = 0;
+    }
+    let seeking = target > time.tick - playback.start_tick;
+    if !seeking {
+        playback.seek_to = None;
+        info!("at tick {target}");
+    }
+    time.fixed_ticks_per_frame = seeking.then_some(1);
+}
+
+/// Turns vsync off while ticks run faster than the display, since a tick a frame is as fast as
+/// vsync allows.
+fn vsync_system(
+    time: Res<GameTime>,
+    mut windows: Option<ResMut<Windows>>,
+    mut vsync: Local<Option<bool>>,
+) {
+    let wanted = time.fixed_ticks_per_frame.is_none() && time.speed <= 1.;
+    if *vsync == Some(wanted) {
+        return;
+    }
+    if let Some(window) = windows
+        .as_mut()
+        .and_then(|windows| windows.get_primary_mut())
+    {
+        window.set_vsync(wanted);
+        *vsync = Some(wanted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording() -> Recording {
+        let mut recording = Recording::new(&LaunchOptions::default());
+        recording.seed = 42;
+        recording.setup.far[0] = Controller::Keyboard(1);
+        recording.points = vec![300, 725];
+        recording.intents = vec![
+            RecordedIntent {
+                tick: 12,
+                player: Player::User,
+                slot: 0,
+                intent: PlayerIntent {
+                    movement: Vec2::new(-0.70710677, 0.70710677),
+                    aim: -1.,
+                    start_charge: true,
+                    release: false,
+                    target: None,
+                },
+            },
+            RecordedIntent {
+                tick: 13,
+                player: Player::Opponent,
+                slot: 0,
+                intent: PlayerIntent {
+                    movement: Vec2::ZERO,
+                    aim: 0.3,
+                    start_charge: false,
+                    release: true,
+                    target: None,
+                },
+            },
+        ];
+        recording
+    }
+
+    #[test]
+    fn recording_round_trips() {
+        let recording = recording();
+        assert_eq!(Recording::parse(&recording.to_text()), Ok(recording));
+    }
+
+    #[test]
+    fn playback_replaces_keyboard_players() {
+        let mut options = LaunchOptions::default();
+        recording().apply(&mut options);
+        assert_eq!(options.seed, 42);
+        assert_eq!(options.match_setup.near[0], Controller::Replay);
+        assert_eq!(options.match_setup.far[0], Controller::Replay);
+        assert_eq!(options.match_setup.far[1], Controller::Cpu);
+    }
+
+    #[test]
+    fn rejects_malformed_recordings() {
+        assert!(Recording::parse("format = standard\n").is_err());
+        assert!(Recording::parse("seed = 1\nnear = user\n").is_err());
+        assert!(Recording::parse("seed = 1\n[intents]\n5 near 0 0 1 0 x\n").is_err());
+        assert!(Recording::parse("seed = 1\n[intents]\n5 middle 0 0 1 0 -\n").is_err());
+    }
+}
@@ -0,0 +1,659 @@
+//! Tennis scoring rules, independent of the ECS.
+//!
+//! [`MatchScore`] is fed the winner of every point and keeps track of points, games and sets
+//! according to a [`MatchFormat`]. The game systems only ever talk to it through
+//! [`MatchScore::point_won`] and the read-only accessors, so everything in here can be unit
+//! tested without an `App`.
+
+use crate::{Player, Preset};
+
+/// Tiebreaks are always won by two points, even under no-ad scoring.
+const TIEBREAK_MARGIN: u32 = 2;
+/// Short sets are played to four games, with a tiebreak at four all.
+const SHORT_SET_GAMES: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Tiebreak {
+    /// Points needed to win the tiebreak.
+    pub(crate) target_score: u32,
+    /// Whether the deciding set also ends in a tiebreak instead of an advantage set.
+    pub(crate) final_set: bool,
+}
+
+/// The rules a match is played under.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct MatchFormat {
+    pub(crate) name: &'static str,
+    /// Points needed to win a game.
+    pub(crate) target_score: u32,
+    /// Lead needed to win a game. Sets use the same margin, capped at the set length.
+    pub(crate) win_margin: u32,
+    pub(crate) games_per_set: u32,
+    /// Best-of-N; must be odd.
+    pub(crate) sets_per_match: u32,
+    /// Played when a set reaches `games_per_set` all, if present.
+    pub(crate) tiebreak: Option<Tiebreak>,
+    /// The first point after deuce wins the game.
+    pub(crate) no_ad: bool,
+    /// Overrides `games_per_set` with short sets to four games.
+    pub(crate) short_sets: bool,
+}
+
+impl Default for MatchFormat {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl MatchFormat {
+    /// Best of three tiebreak sets.
+    pub(crate) fn standard() -> Self {
+        Self {
+            name: "standard",
+            target_score: 4,
+            win_margin: 2,
+            games_per_set: 6,
+            sets_per_match: 3,
+            tiebreak: Some(Tiebreak {
+                target_score: 7,
+                final_set: true,
+            }),
+            no_ad: false,
+            short_sets: false,
+        }
+    }
+
+    /// Best of five tiebreak sets.
+    pub(crate) fn grand_slam() -> Self {
+        Self {
+            name: "grand-slam",
+            sets_per_match: 5,
+            ..Self::standard()
+        }
+    }
+
+    /// Best of three no-ad short sets.
+    pub(crate) fn short() -> Self {
+        Self {
+            name: "short",
+            no_ad: true,
+            short_sets: true,
+            ..Self::standard()
+        }
+    }
+
+    /// A single game: first to seven points, win by two.
+    pub(crate) fn classic() -> Self {
+        Self {
+            name: "classic",
+            target_score: 7,
+            win_margin: 2,
+            games_per_set: 1,
+            sets_per_match: 1,
+            tiebreak: None,
+            no_ad: false,
+            short_sets: false,
+        }
+    }
+
+    /// A single game to three points, for playtesting.
+    pub(crate) fn quick() -> Self {
+        Self {
+            name: "quick",
+            target_score: 3,
+            win_margin: 1,
+            ..Self::classic()
+        }
+    }
+
+    fn set_length(&self) -> u32 {
+        if self.short_sets {
+            SHORT_SET_GAMES
+        } else {
+            self.games_per_set
+        }
+    }
+
+    fn game_margin(&self) -> u32 {
+        if self.no_ad {
+            1
+        } else {
+            self.win_margin
+        }
+    }
+
+    fn set_margin(&self) -> u32 {
+        self.win_margin.min(self.set_length())
+    }
+
+    /// Whether games are called 15/30/40 rather than counted.
+    fn uses_tennis_calls(&self) -> bool {
+        self.target_score == 4
+    }
+}
+
+impl Preset for MatchFormat {
+    fn presets() -> Vec<Self> {
+        vec![
+            Self::standard(),
+            Self::grand_slam(),
+            Self::short(),
+            Self::classic(),
+            Self::quick(),
+        ]
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// A pair of counters, one per side of the court.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Tally {
+    pub(crate) user: u32,
+    pub(crate) opponent: u32,
+}
+
+impl Tally {
+    pub(crate) fn of(&self, player: Player) -> u32 {
+        match player {
+            Player::User => self.user,
+            Player::Opponent => self.opponent,
+        }
+    }
+
+    fn increment(&mut self, player: Player) {
+        match player {
+            Player::User => self.user += 1,
+            Player::Opponent => self.opponent += 1,
+        }
+    }
+
+    /// Returns the leading player if they have at least `target` and lead by `margin`.
+    fn leader(&self, target: u32, margin: u32) -> Option<Player> {
+        [Player::User, Player::Opponent]
+            .into_iter()
+            .find(|&player| {
+                let mine = self.of(player);
+                let theirs = self.of(player.other());
+                mine >= target && mine >= theirs + margin
+            })
+    }
+}
+
+/// The half of the court the server stands in, from their own point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ServeSide {
+    Deuce,
+    Ad,
+}
+
+/// What a single point changed on the scoreboard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ScoreChange {
+    Point,
+    Game(Player),
+    Set(Player),
+    Match(Player),
+    /// The match was already decided and the point was ignored.
+    None,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct MatchScore {
+    format: MatchFormat,
+    first_server: Player,
+    sets: Vec<Tally>,
+    games: Tally,
+    points: Tally,
+    tiebreak: bool,
+    winner: Option<Player>,
+}
+
+impl Default for MatchScore {
+    fn default() -> Self {
+        Self::new(MatchFormat::default(), Player::User)
+    }
+}
+
+impl MatchScore {
+    /// A new match in `format`, in which `first_server` serves the first game.
+    pub(crate) fn new(format: MatchFormat, first_server: Player) -> Self {
+        assert!(
+            format.sets_per_match % 2 == 1,
+            "a match must be played over an odd number of sets"
+        );
+        Self {
+            format,
+            first_server,
+            sets: vec![],
+            games: Tally::default(),
+            points: Tally::default(),
+            tiebreak: false,
+            winner: None,
+        }
+    }
+
+    pub(crate) fn point_won(&mut self, player: Player) -> ScoreChange {
+        if self.winner.is_some() {
+            return ScoreChange::None;
+        }
+        self.points.increment(player);
+        let (target, margin) = match self.format.tiebreak {
+            Some(tiebreak) if self.tiebreak => (tiebreak.target_score, TIEBREAK_MARGIN),
+            _ => (self.format.target_score, self.format.game_margin()),
+        };
+        if self.points.leader(target, margin).is_none() {
+            return ScoreChange::Point;
+        }
+        self.points = Tally::default();
+        self.games.increment(player);
+        let set_length = self.format.set_length();
+        let set_over = self.tiebreak
+            || self
+                .games
+                .leader(set_length, self.format.set_margin())
+                .is_some();
+        if !set_over {
+            self.tiebreak = self.tiebreak_allowed()
+                && self.games.user == set_length
+                && self.games.opponent == set_length;
+            return ScoreChange::Game(player);
+        }
+        self.sets.push(self.games);
+        self.games = Tally::default();
+        self.tiebreak = false;
+        if self.sets_won(player) > self.format.sets_per_match / 2 {
+            self.winner = Some(player);
+            ScoreChange::Match(player)
+        } else {
+            ScoreChange::Set(player)
+        }
+    }
+
+    fn tiebreak_allowed(&self) -> bool {
+        match self.format.tiebreak {
+            Some(tiebreak) => tiebreak.final_set || !self.in_final_set(),
+            None => false,
+        }
+    }
+
+    /// How many times the serve has changed hands so far: once per game, and in a tiebreak
+    /// after the first point and then every two points.
+    fn serve_turn(&self) -> u32 {
+        let games_played = self
+            .sets
+            .iter()
+            .chain(std::iter::once(&self.games))
+            .map(|games| games.user + games.opponent)
+            .sum::<u32>();
+        if self.tiebreak {
+            games_played + (self.points.user + self.points.opponent).div_ceil(2)
+        } else {
+            games_played
+        }
+    }
+
+    /// Who serves the next point.
+    pub(crate) fn server(&self) -> Player {
+        if self.serve_turn().is_multiple_of(2) {
+            self.first_server
+        } else {
+            self.first_server.other()
+        }
+    }
+
+    /// Which member of the serving team serves in doubles. Partners take turns each time
+    /// their team gets the serve back.
+    pub(crate) fn server_slot(&self) -> u8 {
+        (self.serve_turn() / 2 % 2) as u8
+    }
+
+    pub(crate) fn serve_side(&self) -> ServeSide {
+        if (self.points.user + self.points.opponent).is_multiple_of(2) {
+            ServeSide::Deuce
+        } else {
+            ServeSide::Ad
+        }
+    }
+
+    /// Whether the current set decides the match.
+    pub(crate) fn in_final_set(&self) -> bool {
+        let sets_to_win = self.format.sets_per_match / 2 + 1;
+        self.sets_won(Player::User) == sets_to_win - 1
+            && self.sets_won(Player::Opponent) == sets_to_win - 1
+    }
+
+    pub(crate) fn winner(&self) -> Option<Player> {
+        self.winner
+    }
+
+    pub(crate) fn sets_won(&self, player: Player) -> u32 {
+        self.sets
+            .iter()
+            .filter(|set| set.of(player) > set.of(player.other()))
+            .count() as u32
+    }
+
+    /// The point score of `player` in the current game, e.g. `"30"` or `"AD"`.
+    pub(crate) fn point_label(&self, player: Player) -> String {
+        let mine = self.points.of(player);
+        let theirs = self.points.of(player.other());
+        if self.tiebreak || !self.format.uses_tennis_calls() {
+            return mine.to_string();
+        }
+        if mine >= 3 && theirs >= 3 {
+            return match mine.cmp(&theirs) {
+                std::cmp::Ordering::Greater => "AD".to_owned(),
+                std::cmp::Ordering::Equal => "40".to_owned(),
+                std::cmp::Ordering::Less => "".to_owned(),
+            };
+        }
+        match mine {
+            0 => "0",
+            1 => "15",
+            2 => "30",
+            _ => "40",
+        }
+        .to_owned()
+    }
+
+    /// One scoreboard row: finished sets, current games, then the current point.
+    pub(crate) fn scoreboard_row(&self, player: Player) -> String {
+        let mut row = String::new();
+        for set in &self.sets {
+            row += &format!("{} ", set.of(player));
+        }
+        if self.winner.is_none() {
+            row += &format!("{} {:>2}", self.games.of(player), self.point_label(player));
+        }
+        row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn win_points(score: &mut MatchScore, player: Player, n: u32) -> ScoreChange {
+        (0..n).map(|_| score.point_won(player)).last().unwrap()
+    }
+
+    fn win_games(score: &mut MatchScore, player: Player, n: u32) -> ScoreChange {
+        (0..n).map(|_| win_points(score, player, 4)).last().unwrap()
+    }
+
+    #[test]
+    fn love_game() {
+        let mut score = MatchScore::default();
+        assert_eq!(win_points(&mut score, Player::User, 3), ScoreChange::Point);
+        assert_eq!(score.point_label(Player::User), "40");
+        assert_eq!(score.point_label(Player::Opponent), "0");
+        assert_eq!(
+            score.point_won(Player::User),
+            ScoreChange::Game(Player::User)
+        );
+        assert_eq!(
+            score.games,
+            Tally {
+                user: 1,
+                opponent: 0
+            }
+        );
+        assert_eq!(score.points, Tally::default());
+    }
+
+    #[test]
+    fn deuce_and_advantage() {
+        let mut score = MatchScore::default();
+        win_points(&mut score, Player::User, 3);
+        win_points(&mut score, Player::Opponent, 3);
+        assert_eq!(score.point_label(Player::User), "40");
+        assert_eq!(score.point_won(Player::User), ScoreChange::Point);
+        assert_eq!(score.point_label(Player::User), "AD");
+        assert_eq!(score.point_label(Player::Opponent), "");
+        // back to deuce
+        assert_eq!(score.point_won(Player::Opponent), ScoreChange::Point);
+        assert_eq!(score.point_label(Player::Opponent), "40");
+        assert_eq!(score.point_won(Player::Opponent), ScoreChange::Point);
+        assert_eq!(
+            score.point_won(Player::Opponent),
+            ScoreChange::Game(Player::Opponent)
+        );
+    }
+
+    #[test]
+    fn set_needs_two_game_margin() {
+        let mut score = MatchScore::default();
+        win_games(&mut score, Player::User, 5);
+        win_games(&mut score, Player::Opponent, 5);
+        assert_eq!(
+            win_games(&mut score, Player::User, 1),
+            ScoreChange::Game(Player::User)
+        );
+        assert_eq!(
+            win_games(&mut score, Player::User, 1),
+            ScoreChange::Set(Player::User)
+        );
+        assert_eq!(
+            score.sets,
+            &[Tally {
+                user: 7,
+                opponent: 5
+            }]
+        );
+        assert_eq!(score.games, Tally::default());
+    }
+
+    #[test]
+    fn tiebreak_at_six_all() {
+        let mut score = MatchScore::default();
+        win_games(&mut score, Player::User, 5);
+        win_games(&mut score, Player::Opponent, 6);
+        win_games(&mut score, Player::User, 1);
+        assert!(score.tiebreak);
+        win_points(&mut score, Player::User, 6);
+        win_points(&mut score, Player::Opponent, 6);
+        assert_eq!(score.point_label(Player::User), "6");
+        assert_eq!(score.point_won(Player::Opponent), ScoreChange::Point);
+        assert_eq!(
+            score.point_won(Player::Opponent),
+            ScoreChange::Set(Player::Opponent)
+        );
+        assert!(!score.tiebreak);
+        assert_eq!(
+            score.sets,
+            &[Tally {
+                user: 6,
+                opponent: 7
+            }]
+        );
+    }
+
+    #[test]
+    fn best_of_three() {
+        let mut score = MatchScore::new(MatchFormat::standard(), Player::User);
+        win_games(&mut score, Player::User, 6);
+        win_games(&mut score, Player::Opponent, 6);
+        assert_eq!(score.winner(), None);
+        assert_eq!(
+            win_games(&mut score, Player::Opponent, 6),
+            ScoreChange::Match(Player::Opponent)
+        );
+        assert_eq!(score.winner(), Some(Player::Opponent));
+        assert_eq!(score.sets_won(Player::Opponent), 2);
+        assert_eq!(score.point_won(Player::User), ScoreChange::None);
+    }
+
+    #[test]
+    fn scoreboard_rows() {
+        let mut score = MatchScore::new(MatchFormat::standard(), Player::User);
+        win_games(&mut score, Player::User, 6);
+        win_games(&mut score, Player::Opponent, 2);
+        win_points(&mut score, Player::User, 2);
+        assert_eq!(score.scoreboard_row(Player::User), "6 0 30");
+        assert_eq!(score.scoreboard_row(Player::Opponent), "0 2  0");
+    }
+
+    #[test]
+    fn quick_format_is_first_to_three() {
+        let mut score = MatchScore::new(MatchFormat::quick(), Player::User);
+        win_points(&mut score, Player::Opponent, 2);
+        assert_eq!(score.point_label(Player::Opponent), "2");
+        assert_eq!(win_points(&mut score, Player::User, 2), ScoreChange::Point);
+        assert_eq!(
+            score.point_won(Player::User),
+            ScoreChange::Match(Player::User)
+        );
+    }
+
+    #[test]
+    fn classic_format_is_first_to_seven_by_two() {
+        let mut score = MatchScore::new(MatchFormat::classic(), Player::User);
+        win_points(&mut score, Player::User, 6);
+        win_points(&mut score, Player::Opponent, 6);
+        assert_eq!(score.point_won(Player::User), ScoreChange::Point);
+        assert_eq!(
+            score.point_won(Player::User),
+            ScoreChange::Match(Player::User)
+        );
+    }
+
+    #[test]
+    fn no_ad_deuce_is_sudden_death() {
+        let mut score = MatchScore::new(MatchFormat::short(), Player::User);
+        win_points(&mut score, Player::User, 3);
+        win_points(&mut score, Player::Opponent, 3);
+        assert_eq!(
+            score.point_won(Player::Opponent),
+            ScoreChange::Game(Player::Opponent)
+        );
+    }
+
+    #[test]
+    fn short_sets_tiebreak_at_four_all() {
+        let mut score = MatchScore::new(MatchFormat::short(), Player::User);
+        win_games(&mut score, Player::User, 4);
+        assert_eq!(
+            score.sets,
+            &[Tally {
+                user: 4,
+                opponent: 0
+            }]
+        );
+        win_games(&mut score, Player::User, 3);
+        win_games(&mut score, Player::Opponent, 4);
+        win_games(&mut score, Player::User, 1);
+        assert!(score.tiebreak);
+    }
+
+    #[test]
+    fn final_set_without_tiebreak_is_an_advantage_set() {
+        let mut format = MatchFormat::standard();
+        format.tiebreak = Some(Tiebreak {
+            target_score: 7,
+            final_set: false,
+        });
+        let mut score = MatchScore::new(format, Player::User);
+        win_games(&mut score, Player::User, 6);
+        win_games(&mut score, Player::Opponent, 6);
+        assert!(score.in_final_set());
+        win_games(&mut score, Player::User, 5);
+        win_games(&mut score, Player::Opponent, 6);
+        win_games(&mut score, Player::User, 1);
+        assert!(!score.tiebreak);
+        assert_eq!(
+            score.games,
+            Tally {
+                user: 6,
+                opponent: 6
+            }
+        );
+    }
+
+    #[test]
+    fn presets_by_name() {
+        for format in MatchFormat::presets() {
+            assert_eq!(MatchFormat::from_name(format.name), Some(format));
+        }
+        assert_eq!(MatchFormat::from_name("wimbledon"), None);
+    }
+
+    #[test]
+    fn serve_alternates_every_game() {
+        let mut score = MatchScore::default();
+        assert_eq!(score.server(), Player::User);
+        assert_eq!(score.serve_side(), ServeSide::Deuce);
+        score.point_won(Player::Opponent);
+        assert_eq!(score.serve_side(), ServeSide::Ad);
+        win_points(&mut score, Player::Opponent, 3);
+        assert_eq!(score.server(), Player::Opponent);
+        assert_eq!(score.serve_side(), ServeSide::Deuce);
+        win_games(&mut score, Player::User, 5);
+        win_games(&mut score, Player::Opponent, 5);
+        // 11 games played
+        assert_eq!(score.server(), Player::Opponent);
+    }
+
+    #[test]
+    fn opponent_can_serve_first() {
+        let mut score = MatchScore::new(MatchFormat::default(), Player::Opponent);
+        assert_eq!(score.server(), Player::Opponent);
+        win_games(&mut score, Player::User, 1);
+        assert_eq!(score.server(), Player::User);
+        win_games(&mut score, Player::Opponent, 1);
+        assert_eq!(score.server(), Player::Opponent);
+        assert_eq!(score.server_slot(), 1);
+    }
+
+    #[test]
+    fn tiebreak_serve_rotation() {
+        let mut score = MatchScore::default();
+        win_games(&mut score, Player::User, 6);
+        win_games(&mut score, Player::Opponent, 5);
+        win_games(&mut score, Player::User, 6);
+        win_games(&mut score, Player::Opponent, 1);
+        assert!(score.tiebreak);
+        // 6 games in the first set and 12 in this one, so the user serves first
+        let servers: Vec<_> = (0..5)
+            .map(|_| {
+                let server = score.server();
+                score.point_won(Player::User);
+                server
+            })
+            .collect();
+        assert_eq!(
+            servers,
+            [
+                Player::User,
+                Player::Opponent,
+                Player::Opponent,
+                Player::User,
+                Player::User
+            ]
+        );
+    }
+
+    #[test]
+    fn doubles_partners_alternate_serve() {
+        let mut score = MatchScore::default();
+        let servers: Vec<_> = (0..5)
+            .map(|_| {
+                let server = (score.server(), score.server_slot());
+                win_games(&mut score, Player::User, 1);
+                server
+            })
+            .collect();
+        assert_eq!(
+            servers,
+            [
+                (Player::User, 0),
+                (Player::Opponent, 0),
+                (Player::User, 1),
+                (Player::Opponent, 1),
+                (Player::User, 0)
+            ]
+        );
+    }
+}
@@ -1,34 +1,125 @@
 use crate::*;
+use bevy_rapier3d::physics::TimestepMode;
 
-pub(crate) struct SetupPlugin;
+pub(crate) struct SetupPlugin {
+    /// Runs without a window, textures or a GPU: only the game logic, physics and scoring.
+    pub(crate) headless: bool,
+    /// Where loading leads: the main menu, or straight into a match.
+    pub(crate) after_loading: AppState,
+}
+
+struct AfterLoading(AppState);
 
 impl Plugin for SetupPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(DefaultPlugins)
-            // .add_plugin(FrameTimeDiagnosticsPlugin::default())
-            .add_plugin(LogDiagnosticsPlugin::default())
-            .add_plugin(EasingsPlugin)
-            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        if self.headless {
+            // no `LogPlugin`, which can only be added once per process and tests build many
+            // headless apps; `main` adds it
+            app.add_plugins(MinimalPlugins)
+                .add_plugin(TransformPlugin)
+                .add_plugin(bevy::input::InputPlugin)
+                .add_system_set(
+                    SystemSet::on_update(AppState::Loading).with_system(skip_resource_loading),
+                );
+        } else {
+            app.add_plugins(DefaultPlugins)
+                // .add_plugin(FrameTimeDiagnosticsPlugin::default())
+                .add_plugin(LogDiagnosticsPlugin::default())
+                .add_plugin(EasingsPlugin)
+                .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(setup))
+                .add_system_set(
+                    SystemSet::on_update(AppState::Loading).with_system(check_resource_loading),
+                );
+        }
+        app.insert_resource(AfterLoading(self.after_loading.clone()))
+            // single threaded so that systems always run in the same order and ticks reproduce
+            // exactly
+            .add_stage_after(
+                CoreStage::Update,
+                FixedUpdate,
+                SystemStage::single_threaded().with_run_criteria(run_tick),
+            )
             .init_resource::<ResourceHandles>()
-            .init_resource::<UserScore>()
-            .init_resource::<OpponentScore>()
+            .init_resource::<GameTime>()
+            .init_resource::<MatchFormat>()
+            .init_resource::<MatchSetup>()
+            .init_resource::<CourtSurface>()
+            .init_resource::<CpuDifficulty>()
+            .init_resource::<CpuStyle>()
+            .init_resource::<CpuTimers>()
+            .init_resource::<MatchScore>()
             .init_resource::<BallBouncesSinceHit>()
+            .init_resource::<BallTouchedNet>()
+            .init_resource::<RallyState>()
             .add_event::<SpawnBallEvent>()
             .add_event::<SpawnCourtEvent>()
             .add_event::<SpawnPlayerEvent>()
+            .add_event::<SwingEvent>()
             .add_event::<HitEvent>()
+            .add_event::<BounceEvent>()
+            .add_event::<NetTouchEvent>()
+            .add_event::<WallHitEvent>()
+            .add_event::<PlayerTouchEvent>()
             .add_event::<PointOverEvent>()
+            .add_event::<FaultEvent>()
+            .add_event::<LetEvent>()
             .add_event::<GameOverEvent>()
-            .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(setup))
-            .add_system_set(
-                SystemSet::on_update(AppState::Loading).with_system(check_resource_loading),
-            );
+            // after `Time`'s update, an exclusive system at the start of the stage
+            .add_system_to_stage(CoreStage::First, game_time_system)
+            .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(setup_physics));
+    }
+}
+
+fn game_time_system(time: Res<Time>, mut game_time: ResMut<GameTime>) {
+    game_time.frame_ticks = 0;
+    if let Some(ticks) = game_time.fixed_ticks_per_frame {
+        game_time.pending_ticks = ticks;
+    } else if game_time.paused {
+        game_time.pending_ticks = std::mem::take(&mut game_time.step) as u32;
+    } else {
+        let step = game_time.delta();
+        let speed = game_time.speed;
+        game_time.overstep += time.delta().mul_f32(speed);
+        let due = (game_time.overstep.as_nanos() / step.as_nanos()) as u32;
+        game_time.overstep -= step * due;
+        // past the limit the game slows down, rather than taking ever longer frames to catch up
+        game_time.pending_ticks = (game_time.pending_ticks + due).min(MAX_TICKS_PER_FRAME);
+    }
+}
+
+/// Runs the `FixedUpdate` stage once for each tick due this frame.
+fn run_tick(
+    mut game_time: ResMut<GameTime>,
+    state: Res<State<AppState>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) -> ShouldRun {
+    // a tick that queues a state change is the frame's last, so that the next tick runs in the
+    // new state just as it would one tick per frame
+    if game_time.pending_ticks == 0 || (game_time.frame_ticks > 0 && state.is_changed()) {
+        return ShouldRun::No;
     }
+    game_time.pending_ticks -= 1;
+    game_time.frame_ticks += 1;
+    game_time.tick += 1;
+    // the ball holds still while a point's call is shown
+    rapier_config.physics_pipeline_active = *state.current() == AppState::InGame;
+    ShouldRun::YesAndCheckAgain
+}
+
+fn setup_physics(
+    rng: Res<GameRng>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut integration_parameters: ResMut<IntegrationParameters>,
+) {
+    info!("random seed {}", rng.seed);
+    rapier_config.gravity = Vec3::new(0.0, 0.0, -GRAVITY).into();
+    // each run of the step system is one tick's step
+    rapier_config.timestep_mode = TimestepMode::FixedTimestep;
+    integration_parameters.dt = PHYSICS_STEP_SECS;
 }
 
 fn setup(
     mut commands: Commands,
-    mut rapier_config: ResMut<RapierConfiguration>,
     mut texture_handles: ResMut<ResourceHandles>,
     asset_server: Res<AssetServer>,
 ) {
@@ -38,7 +129,6 @@ fn setup(
     commands
         .spawn_bundle(UiCameraBundle::default())
         .insert(UiCamera);
-    rapier_config.gravity = Vec3::new(0.0, 0.0, -15.0).into();
 
     let fonts = ["fonts/Press_Start_2P/PressStart2P-Regular.ttf"];
     let textures = [
@@ -46,6 +136,10 @@ fn setup(
         "textures/player.png",
         "textures/opponent.png",
         "textures/court_grass.png",
+        "textures/court_clay.png",
+        "textures/court_concrete.png",
+        "textures/court_hard_blue.png",
+        "textures/court_hard_green.png",
         "textures/net.png",
     ];
     texture_handles.0.extend(
@@ -57,6 +151,7 @@ fn setup(
 }
 
 fn check_resource_loading(
+    after_loading: Res<AfterLoading>,
     mut state: ResMut<State<AppState>>,
     resource_handles: ResMut<ResourceHandles>,
     asset_server: Res<AssetServer>,
@@ -66,6 +161,10 @@ fn check_resource_loading(
         asset_server.get_group_load_state(handle_ids),
         bevy::asset::LoadState::Loaded
     ) {
-        state.set(AppState::InGame).unwrap();
+        state.set(after_loading.0.clone()).unwrap();
     }
 }
+
+fn skip_resource_loading(after_loading: Res<AfterLoading>, mut state: ResMut<State<AppState>>) {
+    state.set(after_loading.0.clone()).unwrap();
+}
@@ -0,0 +1,200 @@
+//! Batch simulation: plays CPU-only matches back to back as fast as frames go, a batch of
+//! ticks to a frame, then reports who won and how the rallies went and exits.
+//!
+//! Used to balance difficulty presets and physics tweaks without playing the points by hand.
+//! Two `unfair` CPUs never miss, so their rallies, and a batch of their matches, can go on for a
+//! very long time.
+
+use crate::*;
+use bevy::app::AppExit;
+use bevy::utils::HashMap;
+
+pub(crate) struct SimulationPlugin {
+    pub(crate) matches: u32,
+}
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BatchStats {
+            matches: self.matches,
+            ..default()
+        })
+        .add_system(record_points_system)
+        .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(next_match_system));
+    }
+}
+
+#[derive(Default)]
+struct BatchStats {
+    matches: u32,
+    match_wins: [u32; 2],
+    point_wins: [u32; 2],
+    /// Hits in each point played, serves included.
+    rally_hits: Vec<u32>,
+    reasons: HashMap<PointReason, u32>,
+    hits_this_point: u32,
+}
+
+impl BatchStats {
+    fn played(&self) -> u32 {
+        self.match_wins.iter().sum()
+    }
+
+    fn report(&self) -> String {
+        let percent = |count: u32, total: u32| 100. * count as f32 / total.max(1) as f32;
+        let played = self.played();
+        let points = self.point_wins.iter().sum();
+        let mut lines = vec![
+            format!("Simulated {played} matches"),
+            format!(
+                "Matches won: near {} ({:.1}%), far {} ({:.1}%)",
+                self.match_wins[0],
+                percent(self.match_wins[0], played),
+                self.match_wins[1],
+                percent(self.match_wins[1], played),
+            ),
+            format!(
+                "Points won: near {} ({:.1}%), far {} ({:.1}%)",
+                self.point_wins[0],
+                percent(self.point_wins[0], points),
+                self.point_wins[1],
+                percent(self.point_wins[1], points),
+            ),
+        ];
+        if let Some(rallies) = RallySummary::of(&self.rally_hits) {
+            lines.push(format!(
+                "Hits per point: mean {:.2}, median {}, longest {}",
+                rallies.mean, rallies.median, rallies.longest
+            ));
+        }
+        let mut reasons: Vec<_> = self.reasons.iter().collect();
+        reasons.sort_by(|a, b| b.1.cmp(a.1));
+        for (reason, &count) in reasons {
+            lines.push(format!(
+                "  {reason:?}: {count} ({:.1}%)",
+                percent(count, points)
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct RallySummary {
+    mean: f32,
+    median: u32,
+    longest: u32,
+}
+
+impl RallySummary {
+    fn of(hits: &[u32]) -> Option<Self> {
+        let mut sorted = hits.to_vec();
+        sorted.sort_unstable();
+        let longest = *sorted.last()?;
+        Some(Self {
+            mean: sorted.iter().sum::<u32>() as f32 / sorted.len() as f32,
+            median: sorted[sorted.len() / 2],
+            longest,
+        })
+    }
+}
+
+fn side(player: Player) -> usize {
+    match player {
+        Player::User => 0,
+        Player::Opponent => 1,
+    }
+}
+
+fn record_points_system(
+    mut stats: ResMut<BatchStats>,
+    mut hits: EventReader<HitEvent>,
+    mut points: EventReader<PointOverEvent>,
+) {
+    stats.hits_this_point += hits.iter().count() as u32;
+    for ev in points.iter() {
+        let hits = std::mem::take(&mut stats.hits_this_point);
+        stats.rally_hits.push(hits);
+        stats.point_wins[side(ev.winner)] += 1;
+        *stats.reasons.entry(ev.reason).or_default() += 1;
+    }
+}
+
+/// Counts the finished match, then starts a rematch, or reports and exits after the last.
+fn next_match_system(
+    mut stats: ResMut<BatchStats>,
+    score: Res<MatchScore>,
+    mut state: ResMut<State<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // already reported, and exiting
+    if stats.played() == stats.matches {
+        return;
+    }
+    if let Some(winner) = score.winner() {
+        stats.match_wins[side(winner)] += 1;
+        info!(
+            "match {} of {} won by {winner:?}",
+            stats.played(),
+            stats.matches
+        );
+    }
+    if stats.played() < stats.matches {
+        state.replace(AppState::NewMatch).unwrap();
+    } else {
+        println!("{}", stats.report());
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::LaunchOptions;
+    use crate::testing::headless_app;
+
+    /// Two matches between CPUs, first to seven points, cut off after ten minutes of play.
+    fn play_batch() -> BatchStats {
+        const MATCHES: u32 = 2;
+        const MAX_TICKS: u32 = 10 * 60 * 60;
+        let mut options = LaunchOptions {
+            match_format: MatchFormat::classic(),
+            simulate: Some(MATCHES),
+            seed: 7,
+            ..default()
+        };
+        options.match_setup.near = [Controller::Cpu; 2];
+        options.match_setup.far = [Controller::Cpu; 2];
+        let mut app = headless_app(options);
+        app.add_plugin(SimulationPlugin { matches: MATCHES })
+            .add_state(AppState::Loading);
+        for _ in 0..MAX_TICKS {
+            app.update();
+            if app.world.get_resource::<BatchStats>().unwrap().played() == MATCHES {
+                break;
+            }
+        }
+        app.world.remove_resource::<BatchStats>().unwrap()
+    }
+
+    #[test]
+    fn cpus_return_the_ball() {
+        let stats = play_batch();
+        assert_eq!(stats.played(), stats.matches, "{}", stats.report());
+        let rallies = RallySummary::of(&stats.rally_hits).unwrap();
+        assert!(rallies.mean > 1., "{}", stats.report());
+    }
+
+    #[test]
+    fn summarizes_rallies() {
+        assert_eq!(
+            RallySummary::of(&[5, 1, 3, 2, 9]),
+            Some(RallySummary {
+                mean: 4.,
+                median: 3,
+                longest: 9,
+            })
+        );
+        assert_eq!(RallySummary::of(&[]), None);
+    }
+}
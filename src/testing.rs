@@ -0,0 +1,402 @@
+//! Tests that run the whole game headless.
+//!
+//! `TestGame` runs gameplay systems a tick at a time on an empty court, for tests that put a
+//! ball and players where they want them and check what the game makes of it.
+//!
+//! Golden masters: every `.replay` recording in `tests/golden` is played back through
+//! `GamePlugin`, and the calls it comes to (each point with its hits and the score after it,
+//! and each fault and let), and where the players and ball end up are written out and compared
+//! with the `.expected` file beside it. A change that
+//! alters how a recorded match plays out fails with a diff of the two. If the change is
+//! intended, rerun with `BLESS=1` to rewrite the expected files; that is also how a new
+//! recording gets its expected file, which is checked in beside it. Without `BLESS` the test
+//! only reads.
+
+use crate::options::LaunchOptions;
+use crate::replay::{PlaybackPlugin, Recording};
+use crate::*;
+use bevy::app::{Events, ManualEventReader};
+use std::path::{Path, PathBuf};
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+/// Recordings that never finish their match are cut off after five minutes of play.
+const MAX_TICKS: u64 = 5 * 60 * 60;
+
+/// The game as `main` builds it for a headless run with `options`, running a tick on every
+/// `App::update`.
+pub(crate) fn headless_app(options: LaunchOptions) -> App {
+    let options = LaunchOptions {
+        headless: true,
+        ..options
+    };
+    let mut app = game_app(&options, AppState::InGame);
+    app.insert_resource(GameTime::fixed_ticks_per_frame(1));
+    app
+}
+
+// ====== Harness ======
+
+/// A rally under way on an empty court, run a tick at a time. Tests add a ball and players
+/// where they want them, give the players intents, and look at the `HitEvent`s and
+/// `PointOverEvent`s that come of it.
+pub(crate) struct TestGame {
+    app: App,
+    hit_reader: ManualEventReader<HitEvent>,
+    point_reader: ManualEventReader<PointOverEvent>,
+    /// Every hit so far.
+    pub(crate) hits: Vec<HitEvent>,
+    /// Every point ended so far.
+    pub(crate) points: Vec<PointOverEvent>,
+}
+
+impl TestGame {
+    pub(crate) fn new() -> Self {
+        let options = LaunchOptions {
+            seed: 0,
+            ..default()
+        };
+        let mut app = headless_app(options);
+        app.add_state(AppState::Loading);
+        let mut game = Self {
+            app,
+            hit_reader: default(),
+            point_reader: default(),
+            hits: Vec::new(),
+            points: Vec::new(),
+        };
+        // leave loading, then let the court and the match's players spawn
+        game.advance(2);
+        let world = &mut game.app.world;
+        let players: Vec<_> = world
+            .query_filtered::<Entity, Or<(With<Player>, With<Shadow>)>>()
+            .iter(world)
+            .collect();
+        for entity in players {
+            world.despawn(entity);
+        }
+        *game.resource_mut::<RallyState>() = RallyState {
+            phase: RallyPhase::Rally,
+            shots: 2,
+            ..default()
+        };
+        game
+    }
+
+    /// Runs `ticks` ticks, collecting the events they send.
+    pub(crate) fn advance(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.app.update();
+            let world = &self.app.world;
+            let hits = world.get_resource::<Events<HitEvent>>().unwrap();
+            self.hits.extend(self.hit_reader.iter(hits).cloned());
+            let points = world.get_resource::<Events<PointOverEvent>>().unwrap();
+            self.points.extend(self.point_reader.iter(points).cloned());
+        }
+    }
+
+    /// Adds `player`'s first player at `position`, controlled by `set_intent`. Takes a tick.
+    pub(crate) fn spawn_player(&mut self, player: Player, position: Vec3) -> Entity {
+        self.spawn(player, position, Controller::Replay)
+    }
+
+    /// Adds `player`'s first player at `position`, played by the CPU. Takes a tick.
+    pub(crate) fn spawn_cpu(&mut self, player: Player, position: Vec3) -> Entity {
+        self.spawn(player, position, Controller::Cpu)
+    }
+
+    /// Starts the point over with `server` about to serve, instead of mid-rally.
+    pub(crate) fn await_serve(&mut self, server: Player) {
+        *self.resource_mut::<MatchScore>() = MatchScore::new(MatchFormat::default(), server);
+        *self.resource_mut::<RallyState>() = default();
+    }
+
+    fn spawn(&mut self, player: Player, position: Vec3, controller: Controller) -> Entity {
+        let world = &mut self.app.world;
+        let existing: Vec<_> = world
+            .query_filtered::<Entity, With<Player>>()
+            .iter(world)
+            .collect();
+        self.send(SpawnPlayerEvent {
+            position: WorldPosition(position),
+            player,
+            slot: 0,
+            controller,
+        });
+        self.advance(1);
+        let world = &mut self.app.world;
+        world
+            .query::<(Entity, &Player)>()
+            .iter(world)
+            .find(|(entity, spawned)| **spawned == player && !existing.contains(entity))
+            .map(|(entity, _)| entity)
+            .expect("player didn't spawn")
+    }
+
+    /// Adds the ball at `position`, moving at `velocity` and last hit by `hitter`. Takes a tick.
+    pub(crate) fn spawn_ball(&mut self, position: Vec3, velocity: Vec3, hitter: Player) -> Entity {
+        self.send(SpawnBallEvent {
+            position: WorldPosition(position),
+            velocity: RigidBodyVelocity {
+                linvel: velocity.into(),
+                ..default()
+            },
+        });
+        self.advance(1);
+        let world = &mut self.app.world;
+        let ball = world
+            .query_filtered::<Entity, With<GameBall>>()
+            .iter(world)
+            .next()
+            .expect("ball didn't spawn");
+        world.entity_mut(ball).insert(LastHitBy(hitter));
+        ball
+    }
+
+    /// Leaves the ball hanging wherever it's put.
+    pub(crate) fn without_gravity(&mut self) {
+        self.resource_mut::<RapierConfiguration>().gravity = Vec3::ZERO.into();
+    }
+
+    /// What `player` tries to do from the next tick on. Requests are cleared once a tick has
+    /// acted on them, as with any other controller.
+    pub(crate) fn set_intent(&mut self, player: Entity, intent: PlayerIntent) {
+        *self.app.world.get_mut::<PlayerIntent>(player).unwrap() = intent;
+    }
+
+    pub(crate) fn insert<T: Component>(&mut self, entity: Entity, component: T) {
+        self.app.world.entity_mut(entity).insert(component);
+    }
+
+    pub(crate) fn component<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.app.world.get::<T>(entity)
+    }
+
+    /// How many entities have a `T`.
+    pub(crate) fn count<T: Component>(&mut self) -> usize {
+        let world = &mut self.app.world;
+        world.query_filtered::<(), With<T>>().iter(world).count()
+    }
+
+    pub(crate) fn resource_mut<T: Send + Sync + 'static>(&mut self) -> Mut<'_, T> {
+        self.app.world.get_resource_mut::<T>().unwrap()
+    }
+
+    fn send<T: Send + Sync + 'static>(&mut self, event: T) {
+        self.resource_mut::<Events<T>>().send(event);
+    }
+}
+
+// ====== Golden masters ======
+
+#[derive(Default)]
+struct GoldenLog {
+    start_tick: u64,
+    /// A line per point, fault and let.
+    calls: Vec<String>,
+    /// A line per player and the ball, as of the last tick that had players.
+    positions: Vec<String>,
+    game_over: bool,
+}
+
+fn start_log_system(time: Res<GameTime>, mut log: ResMut<GoldenLog>) {
+    log.start_tick = time.tick;
+}
+
+/// Runs last in the frame, after the tick's commands and score updates have landed.
+fn log_system(
+    time: Res<GameTime>,
+    (score, rally): (Res<MatchScore>, Res<RallyState>),
+    mut log: ResMut<GoldenLog>,
+    (mut fault_events, mut let_events, mut point_over_events, mut game_over_events): (
+        EventReader<FaultEvent>,
+        EventReader<LetEvent>,
+        EventReader<PointOverEvent>,
+        EventReader<GameOverEvent>,
+    ),
+    players: Query<(&Player, &PlayerSlot, &WorldPosition), Without<GameBall>>,
+    ball: Query<&WorldPosition, With<GameBall>>,
+) {
+    let tick = time.tick - log.start_tick;
+    let landed = |landing: Option<Vec3>| landing.map_or_else(|| "nowhere".to_owned(), coords);
+    for ev in fault_events.iter() {
+        let line = format!("tick {tick}: fault, landed {}", landed(ev.landing));
+        log.calls.push(line);
+    }
+    for _ in let_events.iter() {
+        log.calls.push(format!("tick {tick}: let"));
+    }
+    for ev in point_over_events.iter() {
+        let line = format!(
+            "tick {tick}: {:?} won, {:?} after {} hit{}, landed {}, last hit by {:?}; score {} / {}",
+            ev.winner,
+            ev.reason,
+            rally.shots,
+            if rally.shots == 1 { "" } else { "s" },
+            landed(ev.landing),
+            ev.last_hitter,
+            score.scoreboard_row(Player::User).trim(),
+            score.scoreboard_row(Player::Opponent).trim(),
+        );
+        log.calls.push(line);
+    }
+    if game_over_events.iter().count() > 0 {
+        log.game_over = true;
+    }
+    let mut positions: Vec<_> = players
+        .iter()
+        .map(|(player, slot, position)| {
+            (
+                *player == Player::Opponent,
+                slot.0,
+                format!("{player:?} {}: {}", slot.0, coords(position.0)),
+            )
+        })
+        .collect();
+    if positions.is_empty() {
+        return;
+    }
+    positions.sort_by_key(|(side, slot, _)| (*side, *slot));
+    log.positions = positions.into_iter().map(|(_, _, line)| line).collect();
+    if let Ok(ball) = ball.get_single() {
+        log.positions.push(format!("ball: {}", coords(ball.0)));
+    }
+    log.positions.insert(0, format!("tick {tick}:"));
+}
+
+fn coords(v: Vec3) -> String {
+    format!("({:.3}, {:.3}, {:.3})", v.x, v.y, v.z)
+}
+
+/// Plays `recording` to the end of its match, or as far as `MAX_TICKS`, and describes how it
+/// went.
+fn play_out(recording: Recording) -> String {
+    let mut options = LaunchOptions::default();
+    recording.apply(&mut options);
+    let mut app = headless_app(options);
+    app.add_plugin(PlaybackPlugin { recording })
+        .init_resource::<GoldenLog>()
+        .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(start_log_system))
+        .add_system_set_to_stage(
+            CoreStage::Last,
+            in_state(AppState::InGame).with_system(log_system),
+        )
+        .add_state(AppState::Loading);
+    // the first update only leaves loading
+    for _ in 0..=MAX_TICKS {
+        app.update();
+        if app.world.get_resource::<GoldenLog>().unwrap().game_over {
+            break;
+        }
+    }
+    let log = app.world.get_resource::<GoldenLog>().unwrap();
+    let ending = if log.game_over {
+        "match over"
+    } else {
+        "cut off"
+    };
+    format!(
+        "[calls]\n{}\n\n[{ending}]\n{}\n",
+        log.calls.join("\n"),
+        log.positions.join("\n"),
+    )
+}
+
+/// A line diff of `expected` against `actual`, showing each change with a line either side.
+fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<_> = expected.lines().collect();
+    let new: Vec<_> = actual.lines().collect();
+    // longest common subsequence of each pair of suffixes
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    let changed = |k: usize| lines.get(k).is_some_and(|(mark, _)| *mark != ' ');
+    let mut text = String::new();
+    let mut skipped = false;
+    for (k, (mark, line)) in lines.iter().enumerate() {
+        if changed(k) || changed(k + 1) || (k > 0 && changed(k - 1)) {
+            if std::mem::take(&mut skipped) {
+                text += "  ...\n";
+            }
+            text += &format!("{mark} {line}\n");
+        } else {
+            skipped = true;
+        }
+    }
+    text
+}
+
+fn recordings() -> Vec<PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(GOLDEN_DIR)
+        .unwrap_or_else(|err| panic!("couldn't read {GOLDEN_DIR}: {err}"))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "replay"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn check_golden(path: &Path, bless: bool) -> Result<(), String> {
+    let recording = Recording::load(&path.to_string_lossy())
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    let actual = play_out(recording);
+    let expected_path = path.with_extension("expected");
+    match std::fs::read_to_string(&expected_path) {
+        Ok(expected) if expected == actual => Ok(()),
+        _ if bless => std::fs::write(&expected_path, &actual)
+            .map_err(|err| format!("couldn't write {}: {err}", expected_path.display())),
+        Ok(expected) => Err(format!(
+            "{} played out differently:\n{}",
+            path.display(),
+            diff(&expected, &actual)
+        )),
+        Err(err) => Err(format!("couldn't read {}: {err}", expected_path.display())),
+    }
+}
+
+#[test]
+fn recorded_matches_play_out_as_before() {
+    let bless = std::env::var_os("BLESS").is_some();
+    let paths = recordings();
+    assert!(!paths.is_empty(), "no recordings in {GOLDEN_DIR}");
+    let failures: Vec<_> = paths
+        .iter()
+        .filter_map(|path| check_golden(path, bless).err())
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{}\nRerun with BLESS=1 if these changes are intended.",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn diffs_show_changes_in_context() {
+    let expected = "a\nb\nc\nd\ne\nf\n";
+    let actual = "a\nb\nC\nd\ne\nf\ng\n";
+    assert_eq!(
+        diff(expected, actual),
+        "  ...\n  b\n- c\n+ C\n  d\n  ...\n  f\n+ g\n"
+    );
+}
@@ -5,23 +5,50 @@ pub(crate) struct UiPlugin;
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app.add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup_ui_text))
-            .add_system_set(
-                SystemSet::on_update(AppState::InGame).with_system(sync_score_text_system),
-            );
+            // in every state, to keep showing the score and the last call under the point over
+            // and game over screens
+            .add_system(sync_score_text_system)
+            .add_system(show_call_system);
     }
 }
 
 fn sync_score_text_system(
-    user_score: ResMut<UserScore>,
-    opponent_score: ResMut<OpponentScore>,
+    score: Res<MatchScore>,
     mut user_text: Query<&mut Text, (With<UserScoreText>, Without<OpponentScoreText>)>,
     mut opponent_text: Query<&mut Text, (With<OpponentScoreText>, Without<UserScoreText>)>,
 ) {
     if let Ok(mut text) = user_text.get_single_mut() {
-        text.sections[0].value = format!("User:     {}", user_score.0);
+        text.sections[0].value = format!("User:     {}", score.scoreboard_row(Player::User));
     }
     if let Ok(mut text) = opponent_text.get_single_mut() {
-        text.sections[0].value = format!("Opponent: {}", opponent_score.0);
+        text.sections[0].value = format!("Opponent: {}", score.scoreboard_row(Player::Opponent));
+    }
+}
+
+/// Shows the umpire's call for faults, lets and the end of each point.
+fn show_call_system(
+    score: Res<MatchScore>,
+    mut point_over_events: EventReader<PointOverEvent>,
+    mut fault_events: EventReader<FaultEvent>,
+    mut let_events: EventReader<LetEvent>,
+    mut results_text: Query<&mut Text, With<ResultsText>>,
+) {
+    let mut call = None;
+    for _ in fault_events.iter() {
+        call = Some("Fault!");
+    }
+    for _ in let_events.iter() {
+        call = Some("Let!");
+    }
+    for ev in point_over_events.iter() {
+        call = Some(ev.reason.call());
+    }
+    // the game over message takes precedence
+    if score.winner().is_some() {
+        return;
+    }
+    if let (Some(call), Ok(mut text)) = (call, results_text.get_single_mut()) {
+        text.sections[0].value = call.to_owned();
     }
 }
 
@@ -39,7 +66,7 @@ fn setup_ui_text(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..default()
             },
             text: Text::with_section(
-                "User:     0",
+                "User:     0  0",
                 TextStyle {
                     font: asset_server.load("fonts/Press_Start_2P/PressStart2P-Regular.ttf"),
                     font_size: 24.0,
@@ -52,7 +79,7 @@ fn setup_ui_text(mut commands: Commands, asset_server: Res<AssetServer>) {
             ),
             ..default()
         })
-        .insert(UserScoreText);
+        .insert_bundle((UserScoreText, SceneEntity));
     commands
         .spawn_bundle(TextBundle {
             style: Style {
@@ -66,7 +93,7 @@ fn setup_ui_text(mut commands: Commands, asset_server: Res<AssetServer>) {
                 ..default()
             },
             text: Text::with_section(
-                "Opponent: 0",
+                "Opponent: 0  0",
                 TextStyle {
                     font: asset_server.load("fonts/Press_Start_2P/PressStart2P-Regular.ttf"),
                     font_size: 24.0,
@@ -79,7 +106,7 @@ fn setup_ui_text(mut commands: Commands, asset_server: Res<AssetServer>) {
             ),
             ..default()
         })
-        .insert(OpponentScoreText);
+        .insert_bundle((OpponentScoreText, SceneEntity));
     commands
         .spawn_bundle(TextBundle {
             style: Style {
@@ -106,5 +133,5 @@ fn setup_ui_text(mut commands: Commands, asset_server: Res<AssetServer>) {
             ),
             ..default()
         })
-        .insert(ResultsText);
+        .insert_bundle((ResultsText, SceneEntity));
 }